@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// A sequence `x0, f(x0), f(f(x0)), ...` that repeats itself after `prefix_length` steps,
+// every `cycle_length` steps
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Cycle {
+    pub prefix_length: usize,
+    pub cycle_length: usize,
+}
+
+impl Cycle {
+    // Earliest step whose state is the same as the one after `n` steps
+    pub fn equivalent_step(&self, n: usize) -> usize {
+        if n < self.prefix_length {
+            n
+        } else {
+            self.prefix_length + (n - self.prefix_length) % self.cycle_length
+        }
+    }
+
+    // Replays the sequence from the start, but never further than one full cycle
+    pub fn nth_state<S>(&self, start: S, mut step: impl FnMut(&S) -> S, n: usize) -> S {
+        (0..self.equivalent_step(n)).fold(start, |state, _| step(&state))
+    }
+}
+
+// Every state is kept, so jumping ahead does not need to replay the sequence
+#[derive(Debug, Clone)]
+pub struct States<S> {
+    pub cycle: Cycle,
+    states: Vec<S>,
+}
+
+impl<S> States<S> {
+    pub fn nth_state(&self, n: usize) -> &S {
+        &self.states[self.cycle.equivalent_step(n)]
+    }
+
+    // The prefix followed by a single cycle
+    pub fn states(&self) -> &[S] {
+        &self.states
+    }
+}
+
+pub fn hashed<S: Hash + Eq + Clone>(start: S, mut step: impl FnMut(&S) -> S) -> States<S> {
+    let mut seen = HashMap::new();
+    let mut states = Vec::new();
+    let mut state = start;
+    loop {
+        if let Some(&first) = seen.get(&state) {
+            return States {
+                cycle: Cycle {
+                    prefix_length: first,
+                    cycle_length: states.len() - first,
+                },
+                states,
+            };
+        }
+        seen.insert(state.clone(), states.len());
+        let next = step(&state);
+        states.push(state);
+        state = next;
+    }
+}
+
+// Brent's algorithm, in constant memory but every state is computed up to three times
+pub fn brent<S: Eq + Copy>(start: S, mut step: impl FnMut(&S) -> S) -> Cycle {
+    let mut power = 1;
+    let mut cycle_length = 1;
+    let mut tortoise = start;
+    let mut hare = step(&start);
+    while tortoise != hare {
+        if power == cycle_length {
+            tortoise = hare;
+            power *= 2;
+            cycle_length = 0;
+        }
+        hare = step(&hare);
+        cycle_length += 1;
+    }
+
+    tortoise = start;
+    hare = (0..cycle_length).fold(start, |state, _| step(&state));
+    let mut prefix_length = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        prefix_length += 1;
+    }
+    Cycle {
+        prefix_length,
+        cycle_length,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pseudo_random(x: &u64) -> u64 {
+        (x * x + 1) % 1009
+    }
+
+    #[test]
+    fn hashed_and_brent_agree() {
+        for start in [0, 1, 2, 17, 500, 1008] {
+            let states = hashed(start, pseudo_random);
+            assert_eq!(brent(start, pseudo_random), states.cycle);
+            assert_eq!(
+                states.states().len(),
+                states.cycle.prefix_length + states.cycle.cycle_length
+            );
+        }
+    }
+
+    #[test]
+    fn pure_cycle() {
+        let next = |x: &u8| (x + 3) % 10;
+        let expected = Cycle {
+            prefix_length: 0,
+            cycle_length: 10,
+        };
+        assert_eq!(hashed(4, next).cycle, expected);
+        assert_eq!(brent(4, next), expected);
+        assert_eq!(
+            brent(7, |&x: &u8| x),
+            Cycle {
+                prefix_length: 0,
+                cycle_length: 1
+            }
+        );
+    }
+
+    #[test]
+    fn nth_state_jumps_ahead() {
+        let states = hashed(2, pseudo_random);
+        let simulated = (0..10_000).fold(2, |x, _| pseudo_random(&x));
+        assert_eq!(*states.nth_state(10_000), simulated);
+        assert_eq!(states.cycle.nth_state(2, pseudo_random, 10_000), simulated);
+        assert_eq!(
+            states.cycle.nth_state(2, pseudo_random, 1_000_000_000),
+            *states.nth_state(1_000_000_000)
+        );
+        assert_eq!(*states.nth_state(0), 2);
+    }
+}
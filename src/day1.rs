@@ -1,6 +1,9 @@
-use aho_corasick::AhoCorasick;
+use aho_corasick::automaton::{Automaton, StateID};
+use aho_corasick::dfa::DFA;
+use aho_corasick::{AhoCorasick, Anchored, MatchKind};
 use anyhow::{anyhow, Result};
 use aoc_runner_derive::{aoc, aoc_generator};
+use std::cmp::Reverse;
 
 fn as_numeric(c: &u8) -> Option<u32> {
     if c.is_ascii_digit() {
@@ -10,62 +13,214 @@ fn as_numeric(c: &u8) -> Option<u32> {
     }
 }
 
-#[aoc_generator(day1, part1)]
-fn parse_part1(input: &[u8]) -> Vec<(u32, u32)> {
+pub fn literal_digits(line: &str) -> Option<(u32, u32)> {
+    let line = line.as_bytes();
+    Some((
+        line.iter().find_map(as_numeric)?,
+        line.iter().rev().find_map(as_numeric)?,
+    ))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlankLines {
+    Reject,
+    Skip,
+}
+
+pub fn calibration_values(
+    input: &str,
+    blank_lines: BlankLines,
+    extract: impl Fn(&str) -> Option<(u32, u32)>,
+) -> Result<Vec<(u32, u32)>> {
     input
-        .split(|&c| c == b'\n')
-        .map(|line| {
-            (
-                line.iter().find_map(as_numeric).unwrap(),
-                line.iter().rev().find_map(as_numeric).unwrap(),
-            )
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| blank_lines == BlankLines::Reject || !line.trim().is_empty())
+        .map(|(n, line)| {
+            extract(line).ok_or_else(|| anyhow!("No digit found on line {}: [{line}]", n + 1))
         })
         .collect()
 }
 
+#[aoc_generator(day1, part1)]
+fn parse_part1(input: &str) -> Result<Vec<(u32, u32)>> {
+    calibration_values(input, BlankLines::Reject, literal_digits)
+}
+
 #[aoc(day1, part1)]
 fn part1(input: &[(u32, u32)]) -> u32 {
     input.iter().map(|(a, b)| a * 10 + b).sum()
 }
 
-const NUMBERS_IN_ENGLISH: &[&str] = &[
-    "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+// Spelled-out digits, literal digits are always recognised on top of them
+pub struct Vocabulary<'a> {
+    words: &'a [(&'a str, u32)],
+}
+
+pub const ENGLISH: Vocabulary<'static> = Vocabulary::new(&[
+    ("one", 1),
+    ("two", 2),
+    ("three", 3),
+    ("four", 4),
+    ("five", 5),
+    ("six", 6),
+    ("seven", 7),
+    ("eight", 8),
+    ("nine", 9),
+]);
+
+pub const FRENCH: Vocabulary<'static> = Vocabulary::new(&[
+    ("un", 1),
+    ("deux", 2),
+    ("trois", 3),
+    ("quatre", 4),
+    ("cinq", 5),
+    ("six", 6),
+    ("sept", 7),
+    ("huit", 8),
+    ("neuf", 9),
+]);
+
+pub const GERMAN: Vocabulary<'static> = Vocabulary::new(&[
+    ("eins", 1),
+    ("zwei", 2),
+    ("drei", 3),
+    ("vier", 4),
+    ("fünf", 5),
+    ("sechs", 6),
+    ("sieben", 7),
+    ("acht", 8),
+    ("neun", 9),
+]);
+
+const LITERALS: &[(&str, u32)] = &[
+    ("0", 0),
+    ("1", 1),
+    ("2", 2),
+    ("3", 3),
+    ("4", 4),
+    ("5", 5),
+    ("6", 6),
+    ("7", 7),
+    ("8", 8),
+    ("9", 9),
 ];
 
-#[aoc_generator(day1, part2)]
-fn parse_part2(input: &str) -> Vec<(u32, u32)> {
-    input
-        .lines()
-        .map(|line| {
-            let mut findings = Vec::new();
-            for (index, number_str) in NUMBERS_IN_ENGLISH.iter().enumerate() {
-                if let Some(pos) = line.find(number_str) {
-                    findings.push((pos, index as u32 + 1));
-                }
-            }
-            for number in 0..10 {
-                if let Some(pos) = line.find::<char>((b'0' + number).into()) {
-                    findings.push((pos, number.into()));
-                }
-            }
-            let &(_, first) = findings.iter().min_by_key(|(pos, _)| pos).unwrap();
+impl<'a> Vocabulary<'a> {
+    pub const fn new(words: &'a [(&'a str, u32)]) -> Self {
+        Vocabulary { words }
+    }
 
-            let mut findings = Vec::new();
-            for (index, number_str) in NUMBERS_IN_ENGLISH.iter().enumerate() {
-                if let Some(pos) = line.rfind(number_str) {
-                    findings.push((pos, index as u32 + 1));
-                }
-            }
-            for number in 0..10 {
-                if let Some(pos) = line.rfind::<char>((b'0' + number).into()) {
-                    findings.push((pos, number.into()));
-                }
-            }
-            let &(_, last) = findings.iter().max_by_key(|(pos, _)| pos).unwrap();
+    fn patterns(&self) -> impl Iterator<Item = (&str, u32)> + '_ {
+        self.words.iter().chain(LITERALS).copied()
+    }
+
+    // Every pattern is searched on its own, so overlapping words such as `eightwo` are found.
+    // When several words start at the same position, the longest one wins.
+    pub fn first_and_last(&self, line: &str) -> Option<(u32, u32)> {
+        let (_, _, first) = self
+            .patterns()
+            .filter_map(|(pattern, value)| Some((line.find(pattern)?, pattern.len(), value)))
+            .min_by_key(|&(pos, len, _)| (pos, Reverse(len)))?;
+        let (_, _, last) = self
+            .patterns()
+            .filter_map(|(pattern, value)| Some((line.rfind(pattern)?, pattern.len(), value)))
+            .max_by_key(|&(pos, len, _)| (pos, len))?;
+        Some((first, last))
+    }
 
-            (first, last)
+    pub fn automaton(&self) -> Result<DigitAutomaton> {
+        let (patterns, values): (Vec<_>, Vec<_>) = self.patterns().unzip();
+        Ok(DigitAutomaton {
+            ac: AhoCorasick::new(patterns)?,
+            values,
         })
-        .collect()
+    }
+
+    pub fn bidirectional(&self) -> Result<BidirectionalAutomaton> {
+        let (patterns, values): (Vec<_>, Vec<_>) = self.patterns().unzip();
+        let reversed = patterns
+            .iter()
+            .map(|pattern| pattern.bytes().rev().collect::<Vec<_>>());
+        let backward = DFA::new(reversed)?;
+        Ok(BidirectionalAutomaton {
+            forward: AhoCorasick::builder()
+                .match_kind(MatchKind::LeftmostLongest)
+                .build(&patterns)?,
+            backward_start: backward.start_state(Anchored::No)?,
+            backward,
+            values,
+        })
+    }
+}
+
+pub struct DigitAutomaton {
+    ac: AhoCorasick,
+    values: Vec<u32>,
+}
+
+impl DigitAutomaton {
+    // Overlapping search, a non-overlapping one would miss the `two` in `eightwo`
+    pub fn first_and_last(&self, line: &str) -> Option<(u32, u32)> {
+        let (first, last) = self
+            .ac
+            .find_overlapping_iter(line)
+            .fold(None, |bounds, found| match bounds {
+                None => Some((found, found)),
+                Some((first, last)) => Some((
+                    if (found.start(), Reverse(found.len())) < (first.start(), Reverse(first.len()))
+                    {
+                        found
+                    } else {
+                        first
+                    },
+                    if (found.start(), found.len()) > (last.start(), last.len()) {
+                        found
+                    } else {
+                        last
+                    },
+                )),
+            })?;
+        Some((self.values[first.pattern()], self.values[last.pattern()]))
+    }
+}
+
+// The backward automaton holds the reversed patterns and is fed the line from its end, so the
+// first match state it reaches holds the words starting last in the line
+pub struct BidirectionalAutomaton {
+    forward: AhoCorasick,
+    backward: DFA,
+    backward_start: StateID,
+    values: Vec<u32>,
+}
+
+impl BidirectionalAutomaton {
+    pub fn first_and_last(&self, line: &str) -> Option<(u32, u32)> {
+        let first = self.forward.find(line)?;
+        Some((self.values[first.pattern()], self.last(line)?))
+    }
+
+    fn last(&self, line: &str) -> Option<u32> {
+        let backward = &self.backward;
+        let mut state = self.backward_start;
+        for &byte in line.as_bytes().iter().rev() {
+            state = backward.next_state(Anchored::No, state, byte);
+            if backward.is_special(state) && backward.is_match(state) {
+                let longest = (0..backward.match_len(state))
+                    .map(|index| backward.match_pattern(state, index))
+                    .max_by_key(|&pattern| backward.pattern_len(pattern))?;
+                return Some(self.values[longest]);
+            }
+        }
+        None
+    }
+}
+
+#[aoc_generator(day1, part2)]
+fn parse_part2(input: &str) -> Result<Vec<(u32, u32)>> {
+    calibration_values(input, BlankLines::Reject, |line| {
+        ENGLISH.first_and_last(line)
+    })
 }
 
 #[aoc(day1, part2)]
@@ -73,29 +228,12 @@ fn part2(input: &[(u32, u32)]) -> u32 {
     input.iter().map(|(a, b)| a * 10 + b).sum()
 }
 
-const NUMBERS_IN_ENGLISH_AND_LITERAL: &[&str] = &[
-    "one", "1", "two", "2", "three", "3", "four", "4", "five", "5", "six", "6", "seven", "7",
-    "eight", "8", "nine", "9",
-];
-
 #[aoc_generator(day1, part2, aho)]
 fn parse_part2_aho_corasick(input: &str) -> Result<Vec<(u32, u32)>> {
-    let ac = AhoCorasick::new(NUMBERS_IN_ENGLISH_AND_LITERAL)?;
-
-    input
-        .lines()
-        .map(|line| {
-            let mut iter = ac.find_iter(line);
-            let first = iter
-                .next()
-                .ok_or_else(|| anyhow!("Could not find any number in {line}"))?;
-            let last = iter.last().unwrap_or(first);
-            Ok((
-                first.pattern().as_u32() / 2 + 1,
-                last.pattern().as_u32() / 2 + 1,
-            ))
-        })
-        .collect()
+    let automaton = ENGLISH.automaton()?;
+    calibration_values(input, BlankLines::Reject, |line| {
+        automaton.first_and_last(line)
+    })
 }
 
 #[aoc(day1, part2, aho)]
@@ -103,18 +241,31 @@ fn part2_aho_corasick(input: &[(u32, u32)]) -> u32 {
     input.iter().map(|(a, b)| a * 10 + b).sum()
 }
 
+#[aoc_generator(day1, part2, bidir)]
+fn parse_part2_bidirectional(input: &str) -> Result<Vec<(u32, u32)>> {
+    let automaton = ENGLISH.bidirectional()?;
+    calibration_values(input, BlankLines::Reject, |line| {
+        automaton.first_and_last(line)
+    })
+}
+
+#[aoc(day1, part2, bidir)]
+fn part2_bidirectional(input: &[(u32, u32)]) -> u32 {
+    input.iter().map(|(a, b)| a * 10 + b).sum()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    const EXAMPLE_PART1: &[u8] = b"1abc2
+    const EXAMPLE_PART1: &str = "1abc2
 pqr3stu8vwx
 a1b2c3d4e5f
 treb7uchet";
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse_part1(EXAMPLE_PART1)), 142);
+        assert_eq!(part1(&parse_part1(EXAMPLE_PART1).unwrap()), 142);
     }
 
     const EXAMPLE_PART2: &str = "two1nine
@@ -127,10 +278,101 @@ zoneight234
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse_part2(EXAMPLE_PART2)), 281);
+        assert_eq!(part2(&parse_part2(EXAMPLE_PART2).unwrap()), 281);
         assert_eq!(
             part2_aho_corasick(&parse_part2_aho_corasick(EXAMPLE_PART2).unwrap()),
             281
         );
+        assert_eq!(
+            part2_bidirectional(&parse_part2_bidirectional(EXAMPLE_PART2).unwrap()),
+            281
+        );
+    }
+
+    fn both_variants(vocabulary: &Vocabulary, line: &str) -> Option<(u32, u32)> {
+        let naive = vocabulary.first_and_last(line);
+        assert_eq!(
+            vocabulary.automaton().unwrap().first_and_last(line),
+            naive,
+            "Variants disagree on [{line}]"
+        );
+        assert_eq!(
+            vocabulary.bidirectional().unwrap().first_and_last(line),
+            naive,
+            "Bidirectional variant disagrees on [{line}]"
+        );
+        naive
+    }
+
+    #[test]
+    fn overlapping_words() {
+        assert_eq!(both_variants(&ENGLISH, "eightwo"), Some((8, 2)));
+        assert_eq!(both_variants(&ENGLISH, "twone"), Some((2, 1)));
+        assert_eq!(both_variants(&ENGLISH, "xtwone3four"), Some((2, 4)));
+        assert_eq!(both_variants(&ENGLISH, "3oneight"), Some((3, 8)));
+        assert_eq!(both_variants(&ENGLISH, "sevenine"), Some((7, 9)));
+        assert_eq!(both_variants(&ENGLISH, "eighthree"), Some((8, 3)));
+        assert_eq!(both_variants(&ENGLISH, "nine"), Some((9, 9)));
+        assert_eq!(both_variants(&ENGLISH, "abc"), None);
+    }
+
+    #[test]
+    fn other_vocabularies() {
+        assert_eq!(both_variants(&FRENCH, "xdeuxtroisy"), Some((2, 3)));
+        assert_eq!(both_variants(&FRENCH, "7huitneuf"), Some((7, 9)));
+        assert_eq!(both_variants(&FRENCH, "one2three"), Some((2, 2)));
+        assert_eq!(both_variants(&GERMAN, "zweins"), Some((2, 1)));
+        assert_eq!(both_variants(&GERMAN, "fünfsechs"), Some((5, 6)));
+        assert_eq!(both_variants(&GERMAN, "achtzig"), Some((8, 8)));
+
+        let custom = Vocabulary::new(&[("ten", 1), ("tenten", 2), ("x", 9)]);
+        assert_eq!(both_variants(&custom, "tententen"), Some((2, 1)));
+        assert_eq!(both_variants(&custom, "ax5tenb"), Some((9, 1)));
+    }
+
+    #[test]
+    fn longest_word_wins() {
+        let all_variants = |words, line| both_variants(&Vocabulary::new(words), line);
+        for words in [
+            &[("tenten", 2), ("ten", 1)][..],
+            &[("ten", 1), ("tenten", 2)][..],
+        ] {
+            assert_eq!(all_variants(words, "tenten"), Some((2, 1)));
+            assert_eq!(all_variants(words, "tententen"), Some((2, 1)));
+            assert_eq!(all_variants(words, "xtenx"), Some((1, 1)));
+        }
+        for words in [
+            &[("seven", 7), ("sevenfold", 1)][..],
+            &[("sevenfold", 1), ("seven", 7)][..],
+        ] {
+            assert_eq!(all_variants(words, "sevenfold"), Some((1, 1)));
+            assert_eq!(all_variants(words, "2sevenfolds"), Some((2, 1)));
+        }
+    }
+
+    #[test]
+    fn lines_without_digits() {
+        let input = "1abc2\n\npqr3stu8vwx";
+        for result in [
+            parse_part1(input),
+            parse_part2(input),
+            parse_part2_aho_corasick(input),
+            parse_part2_bidirectional(input),
+        ] {
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "No digit found on line 2: []"
+            );
+        }
+        assert_eq!(
+            parse_part2("two1nine\nabcdef").unwrap_err().to_string(),
+            "No digit found on line 2: [abcdef]"
+        );
+
+        assert_eq!(
+            calibration_values(input, BlankLines::Skip, literal_digits).unwrap(),
+            vec![(1, 2), (3, 8)]
+        );
+        assert!(calibration_values("1\n  \nabc", BlankLines::Skip, literal_digits).is_err());
     }
 }
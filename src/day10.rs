@@ -1,72 +1,44 @@
+use crate::grid::{walk, Direction, Grid, Position};
 use crate::nom_parser::to_result;
 use anyhow::Result;
 use aoc_runner_derive::{aoc, aoc_generator};
 use std::collections::BTreeSet;
-use std::iter::successors;
-use std::ops::{Add, Index, IndexMut};
 
 #[derive(Debug, Clone)]
 struct Maze {
-    grid: Vec<Vec<Tile>>,
+    grid: Grid<Tile>,
 }
 
 impl Maze {
-    fn start_position(&self) -> MazeIndex {
+    fn start_position(&self) -> Position {
         self.grid
-            .iter()
-            .enumerate()
-            .find_map(|(row, tiles)| {
-                tiles.iter().enumerate().find_map(|(col, &tile)| {
-                    (tile == Tile::StartingPosition).then_some(MazeIndex { row, col })
-                })
-            })
+            .positions()
+            .find(|&position| self.grid[position] == Tile::StartingPosition)
             .unwrap()
     }
-}
-
-impl Index<MazeIndex> for Maze {
-    type Output = Tile;
-
-    fn index(&self, index: MazeIndex) -> &Self::Output {
-        &self.grid[index.row][index.col]
-    }
-}
 
-impl IndexMut<MazeIndex> for Maze {
-    fn index_mut(&mut self, index: MazeIndex) -> &mut Self::Output {
-        &mut self.grid[index.row][index.col]
+    // Cells of the loop in walking order, each with the direction travelled to enter it
+    fn walk_loop(&self, start: Position, direction: Direction) -> Vec<(Position, Direction)> {
+        walk(&self.grid, start, direction, |tile, from| {
+            tile.walk_tile(from).into_iter().collect()
+        })
     }
-}
-
-#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
-struct MazeIndex {
-    row: usize,
-    col: usize,
-}
-
-impl Add<Direction> for MazeIndex {
-    type Output = MazeIndex;
 
-    fn add(self, direction: Direction) -> Self::Output {
-        let MazeIndex { row, col } = self;
-        match direction {
-            Direction::North => MazeIndex { row: row - 1, col },
-            Direction::South => MazeIndex { row: row + 1, col },
-            Direction::West => MazeIndex { col: col - 1, row },
-            Direction::East => MazeIndex { col: col + 1, row },
-        }
+    fn first_step(&self) -> (Position, Direction) {
+        let starting_position = self.start_position();
+        ALL_DIRECTIONS
+            .into_iter()
+            .find_map(|dir| {
+                let first_pos = self.grid.step(starting_position, dir)?;
+                self.grid[first_pos]
+                    .walk_tile(dir)
+                    .map(|_| (first_pos, dir))
+            })
+            .unwrap()
     }
 }
 
-#[derive(Debug, Copy, Clone)]
-enum Direction {
-    North,
-    East,
-    South,
-    West,
-}
-
-// Tests are sensitive to this ordering, there is no overflow check yet
+// Tests are sensitive to this ordering
 const ALL_DIRECTIONS: [Direction; 4] = [
     Direction::East,
     Direction::South,
@@ -119,51 +91,20 @@ fn parse(input: &str) -> Result<Maze> {
 
 #[aoc(day10, part1)]
 fn part1(maze: &Maze) -> usize {
-    let starting_position = maze.start_position();
-    let (first_cell, one_direction) = ALL_DIRECTIONS
-        .into_iter()
-        .find_map(|dir| {
-            let first_pos = starting_position + dir;
-            maze[first_pos].walk_tile(dir).map(|_| (first_pos, dir))
-        })
-        .unwrap();
-
-    let walk = successors(Some((first_cell, one_direction)), |&(index, direction)| {
-        maze[index]
-            .walk_tile(direction)
-            .map(|new_dir| (index + new_dir, new_dir))
-    });
-    walk.count() / 2
+    let (first_cell, one_direction) = maze.first_step();
+    maze.walk_loop(first_cell, one_direction).len() / 2
 }
 
 #[aoc(day10, part2)]
 fn part2(maze: &Maze) -> usize {
     let mut maze = maze.clone();
     let starting_position = maze.start_position();
-    let (first_cell, first_direction) = ALL_DIRECTIONS
-        .into_iter()
-        .find_map(|dir| {
-            let first_pos = starting_position + dir;
-            maze[first_pos].walk_tile(dir).map(|_| (first_pos, dir))
-        })
-        .unwrap();
+    let (first_cell, first_direction) = maze.first_step();
 
-    let walk = successors(
-        Some((first_cell, first_direction)),
-        |&(index, direction)| {
-            maze[index]
-                .walk_tile(direction)
-                .map(|new_dir| (index + new_dir, new_dir))
-        },
-    );
-    let mut loop_indices = BTreeSet::new();
-    let (_, last_direction) = walk
-        .inspect(|&(index, _)| {
-            loop_indices.insert(index);
-        })
-        .last()
-        .unwrap();
-    maze[starting_position] = match (first_direction, last_direction) {
+    let walk = maze.walk_loop(first_cell, first_direction);
+    let loop_indices: BTreeSet<_> = walk.iter().map(|&(index, _)| index).collect();
+    let &(_, last_direction) = walk.last().unwrap();
+    maze.grid[starting_position] = match (first_direction, last_direction) {
         (Direction::East | Direction::West, Direction::East | Direction::West) => Tile::Horizontal,
         (Direction::North | Direction::South, Direction::North | Direction::South) => {
             Tile::Vertical
@@ -175,22 +116,15 @@ fn part2(maze: &Maze) -> usize {
     };
 
     maze.grid
-        .iter()
-        .enumerate()
-        .flat_map(|(row, tiles)| {
-            tiles
-                .iter()
-                .enumerate()
-                .map(move |(col, _)| MazeIndex { row, col })
-        })
+        .positions()
         .filter(|index| !loop_indices.contains(index))
-        .filter(|&MazeIndex { col, row }| {
+        .filter(|&Position { col, row }| {
             (0..col)
                 .rev()
-                .map(|col| MazeIndex { col, row })
+                .map(|col| Position { col, row })
                 .filter(|index| loop_indices.contains(index))
                 .fold((0, None), |(crossings, partial_direction), index| {
-                    match (maze[index], partial_direction) {
+                    match (maze.grid[index], partial_direction) {
                         (Tile::Vertical, _) => (crossings + 1, None),
                         (Tile::Horizontal, partial_direction) => (crossings, partial_direction),
                         (Tile::NorthWest, _) => (crossings, Some(Direction::North)),
@@ -212,6 +146,7 @@ fn part2(maze: &Maze) -> usize {
 
 mod parser {
     use crate::day10::{Maze, Tile};
+    use crate::grid::Grid;
     use nom::branch::alt;
     use nom::bytes::complete::tag;
     use nom::character::complete::newline;
@@ -233,7 +168,9 @@ mod parser {
     }
 
     pub fn maze(input: &str) -> IResult<&str, Maze> {
-        map(separated_list1(newline, many1(tile)), |grid| Maze { grid })(input)
+        map(separated_list1(newline, many1(tile)), |rows| Maze {
+            grid: Grid::new(rows),
+        })(input)
     }
 }
 
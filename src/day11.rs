@@ -1,16 +1,22 @@
 use crate::new_type;
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
-use std::iter::successors;
+use std::cmp::Reverse;
 
 new_type! {
     struct X(usize);
     struct Y(usize);
 }
 
+struct Image {
+    galaxies: Vec<(X, Y)>,
+    width: usize,
+    height: usize,
+}
+
 #[aoc_generator(day11)]
-fn parse(input: &str) -> Vec<(X, Y)> {
-    input
+fn parse(input: &str) -> Image {
+    let galaxies = input
         .lines()
         .enumerate()
         .flat_map(|(y, line)| {
@@ -20,47 +26,128 @@ fn parse(input: &str) -> Vec<(X, Y)> {
                 .filter(|(_, &c)| c == b'#')
                 .map(move |(x, _)| (X(x), Y(y)))
         })
-        .collect()
+        .collect();
+    Image {
+        galaxies,
+        width: input.lines().map(str::len).max().unwrap_or(0),
+        height: input.lines().count(),
+    }
 }
 
-fn solve<const VOID: u64>(galaxies: &[(X, Y)]) -> u64 {
-    let mut seen_x = [false; 140];
-    let mut seen_y = [false; 140];
-    for &(x, y) in galaxies {
-        seen_x[x.0] = true;
-        seen_y[y.0] = true;
+// Position of each coordinate in `0..size` once empty lines are expanded, from the occupied
+// coordinates. The extra last entry is the expanded size.
+fn expanded_coordinates(
+    occupied: impl Iterator<Item = usize>,
+    size: usize,
+    expansion: u64,
+) -> Vec<u64> {
+    let mut seen = vec![false; size];
+    for coordinate in occupied {
+        seen[coordinate] = true;
     }
+    seen.iter()
+        .chain([&true])
+        .scan(0, |position, &seen| {
+            let current = *position;
+            *position += if seen { 1 } else { expansion };
+            Some(current)
+        })
+        .collect()
+}
 
-    galaxies
+fn sum_of_distances(mut positions: Vec<u64>) -> u64 {
+    positions.sort_unstable();
+    let (sum, _) = positions
         .iter()
-        .tuple_combinations()
-        .map(|(&(ax, ay), &(bx, by))| -> u64 {
-            let min_x = ax.min(bx);
-            let diff_x = ax.max(bx) - min_x;
-
-            let min_y = ay.min(by);
-            let diff_y = ay.max(by) - min_y;
-
-            successors(Some(min_x), |&x| Some(x + 1))
-                .map(|x| if seen_x[x.0] { 1u64 } else { VOID })
-                .take(diff_x.into())
-                .sum::<u64>()
-                + successors(Some(min_y), |&y| Some(y + 1))
-                    .map(|y| if seen_y[y.0] { 1u64 } else { VOID })
-                    .take(diff_y.into())
-                    .sum::<u64>()
-        })
-        .sum()
+        .enumerate()
+        .fold((0, 0), |(sum, prefix), (i, &position)| {
+            (sum + position * i as u64 - prefix, prefix + position)
+        });
+    sum
+}
+
+// Galaxies are identified by their index in reading order
+pub struct ExpandedUniverse {
+    galaxies: Vec<(u64, u64)>,
+    width: u64,
+    height: u64,
+}
+
+impl ExpandedUniverse {
+    pub fn new(image: &str, expansion: u64) -> Self {
+        Self::from_image(&parse(image), expansion)
+    }
+
+    fn from_image(image: &Image, expansion: u64) -> Self {
+        let galaxies = &image.galaxies;
+        let expanded_x =
+            expanded_coordinates(galaxies.iter().map(|(x, _)| x.0), image.width, expansion);
+        let expanded_y =
+            expanded_coordinates(galaxies.iter().map(|(_, y)| y.0), image.height, expansion);
+        ExpandedUniverse {
+            galaxies: galaxies
+                .iter()
+                .map(|(x, y)| (expanded_x[x.0], expanded_y[y.0]))
+                .collect(),
+            width: expanded_x[image.width],
+            height: expanded_y[image.height],
+        }
+    }
+
+    pub fn distance(&self, a: usize, b: usize) -> Option<u64> {
+        let &(ax, ay) = self.galaxies.get(a)?;
+        let &(bx, by) = self.galaxies.get(b)?;
+        Some(ax.abs_diff(bx) + ay.abs_diff(by))
+    }
+
+    pub fn distance_matrix(&self) -> Vec<Vec<u64>> {
+        (0..self.galaxies.len())
+            .map(|a| {
+                (0..self.galaxies.len())
+                    .map(|b| self.distance(a, b).unwrap())
+                    .collect()
+            })
+            .collect()
+    }
+
+    // For each galaxy, the nearest and farthest other galaxies (first one in case of a tie)
+    pub fn nearest_and_farthest(&self) -> Vec<Option<(usize, usize)>> {
+        self.distance_matrix()
+            .iter()
+            .enumerate()
+            .map(|(a, distances)| {
+                let others = || distances.iter().enumerate().filter(move |&(b, _)| b != a);
+                let (nearest, _) = others().min_by_key(|&(_, &distance)| distance)?;
+                let (farthest, _) = others().min_by_key(|&(_, &distance)| Reverse(distance))?;
+                Some((nearest, farthest))
+            })
+            .collect()
+    }
+
+    // Only meant for small expansion factors, the image grows with them
+    pub fn render(&self) -> String {
+        let mut image = vec![vec!['.'; self.width as usize]; self.height as usize];
+        for &(x, y) in &self.galaxies {
+            image[y as usize][x as usize] = '#';
+        }
+        image.iter().map(String::from_iter).join("\n")
+    }
+}
+
+fn solve(image: &Image, expansion: u64) -> u64 {
+    let universe = ExpandedUniverse::from_image(image, expansion);
+    sum_of_distances(universe.galaxies.iter().map(|&(x, _)| x).collect())
+        + sum_of_distances(universe.galaxies.iter().map(|&(_, y)| y).collect())
 }
 
 #[aoc(day11, part1)]
-fn part1(galaxies: &[(X, Y)]) -> u64 {
-    solve::<2>(galaxies)
+fn part1(image: &Image) -> u64 {
+    solve(image, 2)
 }
 
 #[aoc(day11, part2)]
-fn part2(galaxies: &[(X, Y)]) -> u64 {
-    solve::<1_000_000>(galaxies)
+fn part2(image: &Image) -> u64 {
+    solve(image, 1_000_000)
 }
 
 #[cfg(test)]
@@ -85,7 +172,77 @@ mod tests {
 
     #[test]
     fn part2_example() {
-        assert_eq!(solve::<10>(&parse(EXAMPLE)), 1030);
-        assert_eq!(solve::<100>(&parse(EXAMPLE)), 8410);
+        assert_eq!(solve(&parse(EXAMPLE), 10), 1030);
+        assert_eq!(solve(&parse(EXAMPLE), 100), 8410);
+    }
+
+    #[test]
+    fn larger_than_140() {
+        let mut image = vec![vec!['.'; 201]; 301];
+        image[0][0] = '#';
+        image[300][200] = '#';
+        let image = image.iter().map(String::from_iter).join("\n");
+        assert_eq!(part1(&parse(&image)), (1 + 199 * 2) + (1 + 299 * 2));
+    }
+
+    #[test]
+    fn render_example() {
+        assert_eq!(
+            ExpandedUniverse::new(EXAMPLE, 2).render(),
+            "....#........
+.........#...
+#............
+.............
+.............
+........#....
+.#...........
+............#
+.............
+.............
+.........#...
+#....#......."
+        );
+    }
+
+    #[test]
+    fn render_empty_edges() {
+        assert_eq!(ExpandedUniverse::new("#.\n..", 2).render(), "#..\n...\n...");
+        assert_eq!(
+            ExpandedUniverse::new("...\n.#.\n...", 3).render(),
+            ".......\n.......\n.......\n...#...\n.......\n.......\n......."
+        );
+        assert_eq!(
+            ExpandedUniverse::new("..\n..", 2).render(),
+            "....\n....\n....\n...."
+        );
+    }
+
+    #[test]
+    fn distance_queries() {
+        let universe = ExpandedUniverse::new(EXAMPLE, 2);
+        assert_eq!(universe.distance(4, 8), Some(9));
+        assert_eq!(universe.distance(0, 6), Some(15));
+        assert_eq!(universe.distance(2, 5), Some(17));
+        assert_eq!(universe.distance(7, 8), Some(5));
+        assert_eq!(universe.distance(7, 9), None);
+
+        let matrix = universe.distance_matrix();
+        assert_eq!(matrix.len(), 9);
+        assert_eq!(matrix.iter().flatten().sum::<u64>(), 2 * 374);
+
+        let extremes = universe.nearest_and_farthest();
+        for (a, extreme) in extremes.into_iter().enumerate() {
+            let (nearest, farthest) = extreme.unwrap();
+            let others = || (0..9).filter(|&b| b != a).map(|b| matrix[a][b]);
+            assert_eq!(matrix[a][nearest], others().min().unwrap());
+            assert_eq!(matrix[a][farthest], others().max().unwrap());
+        }
+
+        let universe = ExpandedUniverse::new(EXAMPLE, 100);
+        assert_eq!(
+            universe.distance_matrix().iter().flatten().sum::<u64>(),
+            2 * 8410
+        );
+        assert_eq!(ExpandedUniverse::new("#", 2).nearest_and_farthest(), [None]);
     }
 }
@@ -1,10 +1,12 @@
+use crate::memo::Memo;
 use crate::nom_parser::to_result;
 use anyhow::Result;
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
 use rayon::prelude::*;
-use std::collections::HashMap;
 use std::iter::once;
+use std::mem;
+use std::str::FromStr;
 
 #[derive(Debug, Clone)]
 struct ConditionRecord {
@@ -12,67 +14,188 @@ struct ConditionRecord {
 }
 
 #[derive(Debug, Clone)]
-struct ConditionEntry {
+pub struct ConditionEntry {
     statuses: Vec<Status>,
     damaged_groups: Vec<u8>,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
-enum Status {
+pub enum Status {
     Operational,
     Damaged,
     Unknown,
 }
 
-// Pointer-based hashkey
-#[derive(Eq, PartialEq, Hash)]
-struct HashKey {
-    ptr1: usize,
-    len1: usize,
-    ptr2: usize,
-    len2: usize,
-}
-
-impl HashKey {
-    fn new(statuses: &[Status], groups: &[u8], taken: u8) -> Self {
-        HashKey {
-            ptr1: statuses.as_ptr() as usize + (taken & 0xF) as usize,
-            len1: statuses.len(),
-            ptr2: groups.as_ptr() as usize + (taken >> 4) as usize,
-            len2: groups.len(),
+impl Status {
+    fn as_char(self) -> char {
+        match self {
+            Status::Operational => '.',
+            Status::Damaged => '#',
+            Status::Unknown => '?',
         }
     }
 }
 
-fn solve(statuses: &[Status], groups: &[u8], taken: u8, cache: &mut HashMap<HashKey, u64>) -> u64 {
-    if let Some(&cached_value) = cache.get(&HashKey::new(statuses, groups, taken)) {
-        cached_value
-    } else {
-        let continue_if_matches =
-            |rest: &[Status], cache: &mut HashMap<HashKey, u64>| match (taken, groups) {
-                (0, [0, groups @ ..]) => solve(rest, groups, 0, cache),
-                (0, groups) => solve(rest, groups, 0, cache),
-                (taken, [group, groups @ ..]) if taken == *group => solve(rest, groups, 0, cache),
-                _ => 0,
-            };
-        let computed_value = match statuses {
-            [Status::Operational, rest @ ..] => continue_if_matches(rest, cache),
-            [Status::Damaged, rest @ ..] => solve(rest, groups, taken + 1, cache),
+// Statuses and groups are always suffixes of the same entry, so their lengths identify them
+type Cache = Memo<(usize, usize, u8), u64>;
+
+fn solve(statuses: &[Status], groups: &[u8], taken: u8, memo: &mut Cache) -> u64 {
+    memo.get_or_compute(
+        (statuses.len(), groups.len(), taken),
+        |memo| match statuses {
+            [Status::Operational, rest @ ..] => close_group(rest, groups, taken, memo),
+            [Status::Damaged, rest @ ..] => solve(rest, groups, taken + 1, memo),
             [Status::Unknown, rest @ ..] => {
-                solve(rest, groups, taken + 1, cache) + continue_if_matches(rest, cache)
+                solve(rest, groups, taken + 1, memo) + close_group(rest, groups, taken, memo)
             }
             [] => match (taken, groups) {
                 (0, []) => 1,
                 (taken, [group]) if taken == *group => 1,
                 _ => 0,
             },
-        };
-        cache.insert(HashKey::new(statuses, groups, taken), computed_value);
-        computed_value
+        },
+    )
+}
+
+// Continues after an operational spring, which ends the current damaged run if any
+fn close_group(rest: &[Status], groups: &[u8], taken: u8, memo: &mut Cache) -> u64 {
+    match (taken, groups) {
+        (0, [0, groups @ ..]) => solve(rest, groups, 0, memo),
+        (0, groups) => solve(rest, groups, 0, memo),
+        (taken, [group, groups @ ..]) if taken == *group => solve(rest, groups, 0, memo),
+        _ => 0,
+    }
+}
+
+fn solve_dp(statuses: &[Status], groups: &[u8]) -> u64 {
+    // State: (position in statuses, position in groups, length of the current damaged run).
+    // Only two rows of statuses are kept, indexed by `group * runs + run`.
+    let runs = groups.iter().copied().max().unwrap_or(0) as usize + 1;
+    let index = |group: usize, run: usize| group * runs + run;
+
+    let mut next = vec![0u64; (groups.len() + 1) * runs];
+    next[index(groups.len(), 0)] = 1;
+    if let Some(&last) = groups.last() {
+        next[index(groups.len() - 1, last as usize)] = 1;
+    }
+
+    let mut current = vec![0u64; next.len()];
+    for &status in statuses.iter().rev() {
+        for group in 0..=groups.len() {
+            for run in 0..runs {
+                let damaged = || match groups.get(group) {
+                    Some(&size) if run < size as usize => next[index(group, run + 1)],
+                    _ => 0,
+                };
+                let operational = || match groups.get(group) {
+                    _ if run == 0 => next[index(group, 0)],
+                    Some(&size) if run == size as usize => next[index(group + 1, 0)],
+                    _ => 0,
+                };
+                current[index(group, run)] = match status {
+                    Status::Operational => operational(),
+                    Status::Damaged => damaged(),
+                    Status::Unknown => operational() + damaged(),
+                };
+            }
+        }
+        mem::swap(&mut current, &mut next);
+    }
+    next[index(0, 0)]
+}
+
+impl ConditionEntry {
+    pub fn unfold(&self, factor: usize, separator: Status) -> ConditionEntry {
+        let statuses = self
+            .statuses
+            .iter()
+            .copied()
+            .chain(once(separator))
+            .cycle()
+            .take(((self.statuses.len() + 1) * factor).saturating_sub(1))
+            .collect_vec();
+        let damaged_groups = self
+            .damaged_groups
+            .iter()
+            .copied()
+            .cycle()
+            .take(self.damaged_groups.len() * factor)
+            .collect_vec();
+        ConditionEntry {
+            statuses,
+            damaged_groups,
+        }
+    }
+
+    pub fn count_arrangements(&self) -> u64 {
+        solve_dp(&self.statuses, &self.damaged_groups)
+    }
+
+    pub fn arrangements(&self, limit: usize) -> impl Iterator<Item = String> + '_ {
+        Arrangements {
+            entry: self,
+            stack: vec![(0, 0, 0, String::with_capacity(self.statuses.len()))],
+        }
+        .take(limit)
+    }
+}
+
+impl FromStr for ConditionEntry {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        to_result(parser::condition_entry(s))
+    }
+}
+
+// Depth-first enumeration, each frame is (position in statuses, position in groups, current run, arrangement so far)
+struct Arrangements<'a> {
+    entry: &'a ConditionEntry,
+    stack: Vec<(usize, usize, u8, String)>,
+}
+
+impl Iterator for Arrangements<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let ConditionEntry {
+            statuses,
+            damaged_groups: groups,
+        } = self.entry;
+        while let Some((position, group, run, mut arrangement)) = self.stack.pop() {
+            let Some(&status) = statuses.get(position) else {
+                let completed = match groups.len().checked_sub(group) {
+                    Some(0) => run == 0,
+                    Some(1) => run == groups[group],
+                    _ => false,
+                };
+                if completed {
+                    return Some(arrangement);
+                }
+                continue;
+            };
+            let damaged = matches!(status, Status::Damaged | Status::Unknown)
+                && groups.get(group).is_some_and(|&size| run < size);
+            let operational = matches!(status, Status::Operational | Status::Unknown)
+                && (run == 0 || groups.get(group) == Some(&run));
+            if damaged {
+                let mut arrangement = arrangement.clone();
+                arrangement.push(Status::Damaged.as_char());
+                self.stack.push((position + 1, group, run + 1, arrangement));
+            }
+            if operational {
+                arrangement.push(Status::Operational.as_char());
+                let group = if run == 0 { group } else { group + 1 };
+                self.stack.push((position + 1, group, 0, arrangement));
+            }
+        }
+        None
     }
 }
 
 #[aoc_generator(day12)]
+#[aoc_generator(day12, part1, dp)]
+#[aoc_generator(day12, part2, dp)]
 fn parse(input: &str) -> Result<ConditionRecord> {
     to_result(parser::condition_record(input))
 }
@@ -82,10 +205,7 @@ fn part1(record: &ConditionRecord) -> u64 {
     record
         .entries
         .iter()
-        .map(|entry| {
-            let mut cache = HashMap::new();
-            solve(&entry.statuses, &entry.damaged_groups, 0, &mut cache)
-        })
+        .map(|entry| solve(&entry.statuses, &entry.damaged_groups, 0, &mut Memo::new()))
         .sum()
 }
 
@@ -95,23 +215,29 @@ fn part2(record: &ConditionRecord) -> u64 {
         .entries
         .par_iter()
         .map(|entry| {
-            let statuses = entry
-                .statuses
-                .iter()
-                .copied()
-                .chain(once(Status::Unknown))
-                .cycle()
-                .take(entry.statuses.len() * 5 + 4)
-                .collect_vec();
-            let damaged_groups = entry
-                .damaged_groups
-                .iter()
-                .copied()
-                .cycle()
-                .take(entry.damaged_groups.len() * 5)
-                .collect_vec();
-            let mut cache = HashMap::new();
-            solve(&statuses, &damaged_groups, 0, &mut cache)
+            let entry = entry.unfold(5, Status::Unknown);
+            solve(&entry.statuses, &entry.damaged_groups, 0, &mut Memo::new())
+        })
+        .sum()
+}
+
+#[aoc(day12, part1, dp)]
+fn part1_dp(record: &ConditionRecord) -> u64 {
+    record
+        .entries
+        .iter()
+        .map(|entry| solve_dp(&entry.statuses, &entry.damaged_groups))
+        .sum()
+}
+
+#[aoc(day12, part2, dp)]
+fn part2_dp(record: &ConditionRecord) -> u64 {
+    record
+        .entries
+        .par_iter()
+        .map(|entry| {
+            let entry = entry.unfold(5, Status::Unknown);
+            solve_dp(&entry.statuses, &entry.damaged_groups)
         })
         .sum()
 }
@@ -134,21 +260,21 @@ mod parser {
         )))(input)
     }
 
-    pub fn condition_record(input: &str) -> IResult<&str, ConditionRecord> {
+    pub fn condition_entry(input: &str) -> IResult<&str, ConditionEntry> {
         map(
-            separated_list1(
-                newline,
-                map(
-                    separated_pair(statuses, tag(" "), separated_list1(tag(","), u8)),
-                    |(statuses, damaged_groups)| ConditionEntry {
-                        statuses,
-                        damaged_groups,
-                    },
-                ),
-            ),
-            |entries| ConditionRecord { entries },
+            separated_pair(statuses, tag(" "), separated_list1(tag(","), u8)),
+            |(statuses, damaged_groups)| ConditionEntry {
+                statuses,
+                damaged_groups,
+            },
         )(input)
     }
+
+    pub fn condition_record(input: &str) -> IResult<&str, ConditionRecord> {
+        map(separated_list1(newline, condition_entry), |entries| {
+            ConditionRecord { entries }
+        })(input)
+    }
 }
 
 #[cfg(test)]
@@ -171,8 +297,15 @@ mod tests {
                 "Failed at line {} [{line}]",
                 i + 1
             );
+            assert_eq!(
+                part1_dp(&parse(line).unwrap()),
+                expected,
+                "Failed at line {} [{line}] (dp)",
+                i + 1
+            );
         }
         assert_eq!(part1(&parse(EXAMPLE).unwrap()), 21);
+        assert_eq!(part1_dp(&parse(EXAMPLE).unwrap()), 21);
     }
 
     #[test]
@@ -188,7 +321,93 @@ mod tests {
                 "Failed at line {} [{line}]",
                 i + 1
             );
+            assert_eq!(
+                part2_dp(&parse(line).unwrap()),
+                expected,
+                "Failed at line {} [{line}] (dp)",
+                i + 1
+            );
         }
         assert_eq!(part2(&parse(EXAMPLE).unwrap()), 525152);
+        assert_eq!(part2_dp(&parse(EXAMPLE).unwrap()), 525152);
+    }
+
+    fn brute_force(entry: &ConditionEntry) -> Vec<String> {
+        let unknowns = entry
+            .statuses
+            .iter()
+            .filter(|&&status| status == Status::Unknown)
+            .count();
+        (0..1u32 << unknowns)
+            .map(|mut mask| {
+                entry
+                    .statuses
+                    .iter()
+                    .map(|&status| match status {
+                        Status::Unknown => {
+                            let damaged = mask & 1 == 1;
+                            mask >>= 1;
+                            if damaged {
+                                '#'
+                            } else {
+                                '.'
+                            }
+                        }
+                        status => status.as_char(),
+                    })
+                    .collect::<String>()
+            })
+            .filter(|arrangement| {
+                arrangement
+                    .split('.')
+                    .filter(|group| !group.is_empty())
+                    .map(|group| group.len() as u8)
+                    .eq(entry.damaged_groups.iter().copied())
+            })
+            .sorted()
+            .collect()
+    }
+
+    #[test]
+    fn arrangements_match_brute_force() {
+        let all_statuses = (1..=6).flat_map(|len| {
+            (0..len)
+                .map(|_| ['.', '#', '?'])
+                .multi_cartesian_product()
+                .map(String::from_iter)
+        });
+        for statuses in all_statuses {
+            for groups in ["1", "2", "1,1", "2,1", "1,3", "1,1,1"] {
+                let line = format!("{statuses} {groups}");
+                let entry: ConditionEntry = line.parse().unwrap();
+                let expected = brute_force(&entry);
+                assert_eq!(
+                    entry.count_arrangements(),
+                    expected.len() as u64,
+                    "Failed at [{line}]"
+                );
+                assert_eq!(
+                    entry.arrangements(usize::MAX).sorted().collect_vec(),
+                    expected,
+                    "Failed at [{line}]"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unfold_with_custom_factor_and_separator() {
+        let entry: ConditionEntry = ".# 1".parse().unwrap();
+        let unfolded = entry.unfold(3, Status::Operational);
+        assert_eq!(unfolded.arrangements(10).collect_vec(), [".#..#..#"]);
+        assert_eq!(unfolded.count_arrangements(), 1);
+
+        let entry: ConditionEntry = "???.### 1,1,3".parse().unwrap();
+        assert_eq!(entry.unfold(1, Status::Unknown).count_arrangements(), 1);
+        assert_eq!(entry.unfold(5, Status::Unknown).count_arrangements(), 1);
+
+        let entry: ConditionEntry = "?###???????? 3,2,1".parse().unwrap();
+        assert_eq!(entry.arrangements(3).count(), 3);
+        assert_eq!(entry.arrangements(usize::MAX).count(), 10);
     }
 }
@@ -1,116 +1,314 @@
-use crate::day2::parser::games;
-use anyhow::Result;
+use crate::nom_parser::to_result;
+use anyhow::{Context, Result};
 use aoc_runner_derive::{aoc, aoc_generator};
-use std::collections::HashMap;
+use smallvec::SmallVec;
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::str::FromStr;
+
+pub struct CubeGames {
+    colors: Colors,
+    games: Vec<Game>,
+}
 
 struct Game {
     id: u32,
     set_of_cubes: Vec<SetOfCubes>,
 }
 
+// Cube counts indexed by colour, None for colours that were not shown at all. Only spills to the
+// heap past eight colours.
 #[derive(Default)]
-struct SetOfCubes(HashMap<Color, u32>);
+struct SetOfCubes(SmallVec<[Option<u32>; 8]>);
 
 impl SetOfCubes {
+    fn get(&self, Color(index): Color) -> Option<u32> {
+        self.0.get(index).copied().flatten()
+    }
+
+    fn set(&mut self, Color(index): Color, count: u32) {
+        if self.0.len() <= index {
+            self.0.resize(index + 1, None);
+        }
+        self.0[index] = Some(count);
+    }
+
     fn contains(&self, other: &Self) -> bool {
         other
             .0
             .iter()
-            .all(|(color, &count)| self.0.get(color).copied().unwrap_or_default() >= count)
+            .enumerate()
+            .all(|(index, theirs)| self.get(Color(index)).unwrap_or(0) >= theirs.unwrap_or(0))
+    }
+
+    // None is smaller than any count
+    fn union(mut self, other: &Self) -> Self {
+        if self.0.len() < other.0.len() {
+            self.0.resize(other.0.len(), None);
+        }
+        for (mine, &theirs) in self.0.iter_mut().zip(&other.0) {
+            *mine = (*mine).max(theirs);
+        }
+        self
+    }
+
+    fn power(&self) -> u32 {
+        self.0.iter().flatten().product()
+    }
+}
+
+#[derive(Copy, Clone, Hash, Eq, PartialEq, Debug)]
+struct Color(usize);
+
+// Interned colour names, a `Color` is an index in `names`
+#[derive(Default)]
+struct Colors {
+    names: Vec<String>,
+}
+
+impl Colors {
+    fn intern(&mut self, name: &str) -> Color {
+        self.get(name).unwrap_or_else(|| {
+            self.names.push(name.to_string());
+            Color(self.names.len() - 1)
+        })
+    }
+
+    fn get(&self, name: &str) -> Option<Color> {
+        self.names.iter().position(|n| n == name).map(Color)
+    }
+
+    fn name(&self, Color(index): Color) -> &str {
+        &self.names[index]
+    }
+}
+
+impl CubeGames {
+    fn bag(&self, bag: &[(&str, u32)]) -> SetOfCubes {
+        let mut set_of_cubes = SetOfCubes::default();
+        // Colours that never show up in the games cannot matter
+        for &(name, count) in bag {
+            if let Some(color) = self.colors.get(name) {
+                set_of_cubes.set(color, count);
+            }
+        }
+        set_of_cubes
+    }
+
+    fn named(&self, set_of_cubes: &SetOfCubes) -> BTreeMap<&str, u32> {
+        set_of_cubes
+            .0
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &count)| Some((self.colors.name(Color(index)), count?)))
+            .collect()
+    }
+
+    fn minimal_sets(&self) -> impl Iterator<Item = (u32, SetOfCubes)> + '_ {
+        self.games.iter().map(|game| {
+            let minimal_set = game
+                .set_of_cubes
+                .iter()
+                .fold(SetOfCubes::default(), SetOfCubes::union);
+            (game.id, minimal_set)
+        })
+    }
+
+    pub fn feasibility(&self, bag: &[(&str, u32)]) -> Vec<(u32, bool)> {
+        let bag = self.bag(bag);
+        self.games
+            .iter()
+            .map(|game| {
+                let feasible = game
+                    .set_of_cubes
+                    .iter()
+                    .all(|set_of_cubes| bag.contains(set_of_cubes));
+                (game.id, feasible)
+            })
+            .collect()
+    }
+
+    pub fn minimal_bags(&self) -> Vec<(u32, BTreeMap<&str, u32>)> {
+        self.minimal_sets()
+            .map(|(id, minimal_set)| (id, self.named(&minimal_set)))
+            .collect()
+    }
+
+    pub fn maxima(&self) -> BTreeMap<&str, u32> {
+        let maxima = self
+            .minimal_sets()
+            .fold(SetOfCubes::default(), |maxima, (_, minimal_set)| {
+                maxima.union(&minimal_set)
+            });
+        self.named(&maxima)
+    }
+}
+
+impl FromStr for CubeGames {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut reader = GameReader::new(s.as_bytes());
+        let games = reader.by_ref().collect::<Result<_>>()?;
+        Ok(CubeGames {
+            colors: reader.colors,
+            games,
+        })
+    }
+}
+
+// Parses one game per line, reusing the same line buffer
+struct GameReader<R> {
+    reader: R,
+    colors: Colors,
+    line: String,
+    line_number: usize,
+}
+
+impl<R: BufRead> GameReader<R> {
+    fn new(reader: R) -> Self {
+        GameReader {
+            reader,
+            colors: Colors::default(),
+            line: String::new(),
+            line_number: 0,
+        }
+    }
+
+    fn read_game(&mut self) -> Result<Option<Game>> {
+        loop {
+            self.line.clear();
+            self.line_number += 1;
+            if self.reader.read_line(&mut self.line)? == 0 {
+                return Ok(None);
+            }
+            let line = self.line.trim_end();
+            if !line.is_empty() {
+                return to_result(parser::game(line, &mut self.colors))
+                    .with_context(|| format!("Invalid game on line {}", self.line_number))
+                    .map(Some);
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for GameReader<R> {
+    type Item = Result<Game>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_game().transpose()
+    }
+}
+
+pub fn sum_feasible_ids(reader: impl BufRead, bag: &[(&str, u32)]) -> Result<u32> {
+    let mut games = GameReader::new(reader);
+    // Bag counts of the colours seen so far, colours missing from the bag have no cubes
+    let mut counts = SetOfCubes::default();
+    let mut sum = 0;
+    while let Some(game) = games.read_game()? {
+        for index in counts.0.len()..games.colors.names.len() {
+            let name = games.colors.name(Color(index));
+            let count = bag.iter().find(|&&(n, _)| n == name).map_or(0, |&(_, c)| c);
+            counts.set(Color(index), count);
+        }
+        let feasible = game
+            .set_of_cubes
+            .iter()
+            .all(|set_of_cubes| counts.contains(set_of_cubes));
+        if feasible {
+            sum += game.id;
+        }
     }
+    Ok(sum)
 }
 
-#[derive(Copy, Clone, Hash, Eq, PartialEq)]
-enum Color {
-    Red,
-    Green,
-    Blue,
+pub fn sum_of_powers(reader: impl BufRead) -> Result<u32> {
+    GameReader::new(reader).try_fold(0, |sum, game| {
+        let minimal_set = game?
+            .set_of_cubes
+            .iter()
+            .fold(SetOfCubes::default(), SetOfCubes::union);
+        Ok(sum + minimal_set.power())
+    })
 }
 
 #[aoc_generator(day2)]
-fn parse(input: &str) -> Result<Vec<Game>> {
-    let (_, games) = games(input).map_err(|err| {
-        err.map(|error| nom::error::Error::new(error.input.to_string(), error.code))
-    })?;
-    Ok(games)
+fn parse(input: &str) -> Result<CubeGames> {
+    input.parse()
 }
 
+const BAG: &[(&str, u32)] = &[("red", 12), ("green", 13), ("blue", 14)];
+
 #[aoc(day2, part1)]
-fn part1(games: &[Game]) -> u32 {
-    let bag = SetOfCubes(
-        [(Color::Red, 12), (Color::Green, 13), (Color::Blue, 14)]
-            .into_iter()
-            .collect(),
-    );
+fn part1(games: &CubeGames) -> u32 {
     games
-        .iter()
-        .filter(|game| {
-            game.set_of_cubes
-                .iter()
-                .all(|set_of_cubes| bag.contains(set_of_cubes))
-        })
-        .map(|game| game.id)
+        .feasibility(BAG)
+        .into_iter()
+        .filter(|&(_, feasible)| feasible)
+        .map(|(id, _)| id)
         .sum()
 }
 
 #[aoc(day2, part2)]
-fn part2(games: &[Game]) -> u32 {
+fn part2(games: &CubeGames) -> u32 {
     games
-        .iter()
-        .map(|game| {
-            game.set_of_cubes
-                .iter()
-                .fold(SetOfCubes::default(), |mut minimal_set, set_of_cubes| {
-                    for (&color, &count) in &set_of_cubes.0 {
-                        let entry = minimal_set.0.entry(color).or_insert(0);
-                        *entry = u32::max(*entry, count);
-                    }
-                    minimal_set
-                })
-        })
-        .map(|minimal_set| minimal_set.0.values().product::<u32>())
+        .minimal_sets()
+        .map(|(_, minimal_set)| minimal_set.power())
         .sum()
 }
 
+// Named solutions would otherwise receive the parsed games
+#[aoc_generator(day2, part1, streaming)]
+#[aoc_generator(day2, part2, streaming)]
+fn raw_input(input: &[u8]) -> Vec<u8> {
+    input.to_vec()
+}
+
+#[aoc(day2, part1, streaming)]
+fn part1_streaming(input: &[u8]) -> Result<u32> {
+    sum_feasible_ids(input, BAG)
+}
+
+#[aoc(day2, part2, streaming)]
+fn part2_streaming(input: &[u8]) -> Result<u32> {
+    sum_of_powers(input)
+}
+
 mod parser {
-    use crate::day2::{Color, Game, SetOfCubes};
+    use crate::day2::{Color, Colors, Game, SetOfCubes};
     use crate::nom_parser::number;
-    use nom::branch::alt;
     use nom::bytes::complete::tag;
-    use nom::multi::{separated_list0, separated_list1};
+    use nom::character::complete::alpha1;
+    use nom::multi::separated_list1;
     use nom::IResult;
-    use nom::Parser;
 
-    fn color(input: &str) -> IResult<&str, (Color, u32)> {
+    fn color<'a>(input: &'a str, colors: &mut Colors) -> IResult<&'a str, (Color, u32)> {
         let (input, count) = number(input)?;
         let (input, _) = tag(" ")(input)?;
-        let (input, color) = alt((
-            tag("red").map(|_| Color::Red),
-            tag("green").map(|_| Color::Green),
-            tag("blue").map(|_| Color::Blue),
-        ))(input)?;
-        Ok((input, (color, count)))
+        let (input, name) = alpha1(input)?;
+        Ok((input, (colors.intern(name), count)))
     }
 
-    fn set_of_cubes(input: &str) -> IResult<&str, SetOfCubes> {
-        let (input, colors) = separated_list1(tag(", "), color)(input)?;
+    fn set_of_cubes<'a>(input: &'a str, colors: &mut Colors) -> IResult<&'a str, SetOfCubes> {
+        let (input, colors) = separated_list1(tag(", "), |input| color(input, colors))(input)?;
 
-        Ok((input, SetOfCubes(colors.into_iter().collect())))
+        let mut set_of_cubes = SetOfCubes::default();
+        for (color, count) in colors {
+            set_of_cubes.set(color, count);
+        }
+        Ok((input, set_of_cubes))
     }
 
-    fn game(input: &str) -> IResult<&str, Game> {
+    pub fn game<'a>(input: &'a str, colors: &mut Colors) -> IResult<&'a str, Game> {
         let (input, _) = tag("Game ")(input)?;
         let (input, id) = number(input)?;
         let (input, _) = tag(": ")(input)?;
 
-        let (input, set_of_cubes) = separated_list1(tag("; "), set_of_cubes)(input)?;
+        let (input, set_of_cubes) =
+            separated_list1(tag("; "), |input| set_of_cubes(input, colors))(input)?;
 
         Ok((input, Game { id, set_of_cubes }))
     }
-
-    pub fn games(input: &str) -> IResult<&str, Vec<Game>> {
-        separated_list0(tag("\n"), game)(input)
-    }
 }
 
 #[cfg(test)]
@@ -126,10 +324,94 @@ Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green";
     #[test]
     fn part1_example() {
         assert_eq!(part1(&parse(EXAMPLE).unwrap()), 8);
+        assert_eq!(part1_streaming(EXAMPLE.as_bytes()).unwrap(), 8);
     }
 
     #[test]
     fn part2_example() {
         assert_eq!(part2(&parse(EXAMPLE).unwrap()), 2286);
+        assert_eq!(part2_streaming(EXAMPLE.as_bytes()).unwrap(), 2286);
+    }
+
+    const FOUR_COLORS: &str = "Game 1: 3 blue, 4 red, 1 yellow; 1 red, 2 green
+Game 2: 2 yellow; 5 yellow, 1 blue
+Game 3: 1 green, 1 red, 1 blue";
+
+    #[test]
+    fn four_colors() {
+        let games: CubeGames = FOUR_COLORS.parse().unwrap();
+        assert_eq!(
+            games.feasibility(&[("red", 4), ("green", 2), ("blue", 3), ("yellow", 2)]),
+            [(1, true), (2, false), (3, true)]
+        );
+        assert_eq!(
+            games.feasibility(&[("red", 4), ("green", 2), ("blue", 3)]),
+            [(1, false), (2, false), (3, true)]
+        );
+        assert_eq!(
+            games.minimal_bags()[1],
+            (2, BTreeMap::from([("blue", 1), ("yellow", 5)]))
+        );
+        assert_eq!(
+            games.maxima(),
+            BTreeMap::from([("blue", 3), ("green", 2), ("red", 4), ("yellow", 5)])
+        );
+        assert_eq!(part2(&games), 3 * 4 * 2 + 5 + 1);
+        assert_eq!(
+            sum_of_powers(FOUR_COLORS.as_bytes()).unwrap(),
+            3 * 4 * 2 + 5 + 1
+        );
+        assert_eq!(
+            sum_feasible_ids(
+                FOUR_COLORS.as_bytes(),
+                &[("red", 4), ("green", 2), ("blue", 3)]
+            )
+            .unwrap(),
+            3
+        );
+    }
+
+    #[test]
+    fn streaming_errors() {
+        let error = sum_of_powers("Game 1: 1 red\n\nGame 2: red\n".as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid game on line 3");
+        assert_eq!(sum_of_powers("Game 1: 1 red\n\n".as_bytes()).unwrap(), 1);
+    }
+
+    #[test]
+    fn many_colors() {
+        let eight_colors = "Game 1: 1 a, 1 b, 1 c, 1 d; 1 e, 1 f, 1 g, 2 h\nGame 2: 1 red";
+        let games: CubeGames = eight_colors.parse().unwrap();
+        assert_eq!(games.feasibility(BAG), [(1, false), (2, true)]);
+        assert_eq!(sum_feasible_ids(eight_colors.as_bytes(), BAG).unwrap(), 2);
+        let bag = &[
+            ("a", 1),
+            ("b", 1),
+            ("c", 1),
+            ("d", 1),
+            ("e", 1),
+            ("f", 1),
+            ("g", 1),
+            ("h", 2),
+        ];
+        assert_eq!(sum_feasible_ids(eight_colors.as_bytes(), bag).unwrap(), 1);
+
+        let eleven_colors = "Game 1: 1 a, 1 b, 1 c, 1 d; 1 e, 1 f, 1 g, 1 h, 1 i; 2 j, 3 k";
+        assert_eq!(sum_of_powers(eleven_colors.as_bytes()).unwrap(), 6);
+        let games: CubeGames = eleven_colors.parse().unwrap();
+        assert_eq!(games.maxima().len(), 11);
+        assert_eq!(games.maxima()["k"], 3);
+    }
+
+    #[test]
+    fn zero_counts() {
+        let zero_red = "Game 1: 0 red, 2 blue; 3 green";
+        assert_eq!(sum_of_powers(zero_red.as_bytes()).unwrap(), 0);
+        let games: CubeGames = zero_red.parse().unwrap();
+        assert_eq!(part2(&games), 0);
+        assert_eq!(
+            games.maxima(),
+            BTreeMap::from([("blue", 2), ("green", 3), ("red", 0)])
+        );
     }
 }
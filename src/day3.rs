@@ -1,27 +1,36 @@
 use anyhow::Result;
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::{Either, Itertools};
+use std::collections::{HashMap, HashSet};
 
-#[derive(Copy, Clone, Debug)]
-struct Position {
-    row: usize,
-    col: usize,
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Position {
+    pub row: usize,
+    pub col: usize,
 }
 
 #[derive(Copy, Clone, Debug)]
-struct Span {
+pub struct Span {
     position: Position,
     length: usize,
 }
 
 #[derive(Copy, Clone, Debug)]
-struct Number {
+pub struct Number {
     value: u32,
     span: Span,
 }
 
 impl Number {
-    fn is_adjacent_to(&self, symbol: &Symbol) -> bool {
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+
+    pub fn position(&self) -> Position {
+        self.span.position
+    }
+
+    fn border(&self) -> impl Iterator<Item = Position> {
         let Number {
             span:
                 Span {
@@ -30,68 +39,248 @@ impl Number {
                 },
             ..
         } = *self;
-        (row.saturating_sub(1)..=(row + 1)).contains(&symbol.position.row)
-            && (col.saturating_sub(1)..=(col + length)).contains(&symbol.position.col)
+        (row.saturating_sub(1)..=row + 1)
+            .cartesian_product(col.saturating_sub(1)..=col + length)
+            .map(|(row, col)| Position { row, col })
     }
 }
 
 #[derive(Copy, Clone, Debug)]
-struct Symbol {
+pub struct Symbol {
     char: char,
     position: Position,
 }
 
+impl Symbol {
+    pub fn char(&self) -> char {
+        self.char
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
-enum Element {
+pub enum Element {
     Number(Number),
     Symbol(Symbol),
 }
 
+// The elements along with the lines they were read from, so that the grid can be redrawn as is
+pub struct Drawing {
+    elements: Vec<Element>,
+    lines: Vec<String>,
+}
+
+impl Drawing {
+    pub fn elements(&self) -> &[Element] {
+        &self.elements
+    }
+}
+
 #[aoc_generator(day3)]
-fn parse(input: &str) -> Result<Vec<Element>> {
+pub fn parse(input: &str) -> Result<Drawing> {
     let (_, elements) = parser::elements(input).map_err(|err| {
         err.map(|error| nom::error::Error::new(error.input.to_string(), error.code))
     })?;
-    Ok(elements)
+    Ok(Drawing {
+        elements,
+        lines: input.lines().map(str::to_string).collect(),
+    })
+}
+
+pub struct Schematic {
+    numbers: Vec<Number>,
+    symbols: HashMap<Position, Symbol>,
+    // Indices in `numbers` of the numbers bordering each symbol
+    neighbours: HashMap<Position, Vec<usize>>,
+}
+
+impl Schematic {
+    pub fn new(elements: &[Element]) -> Self {
+        let (numbers, symbols): (Vec<Number>, HashMap<Position, Symbol>) =
+            elements.iter().partition_map(|element| match element {
+                Element::Number(number) => Either::Left(*number),
+                Element::Symbol(symbol) => Either::Right((symbol.position, *symbol)),
+            });
+        let mut neighbours: HashMap<_, Vec<_>> = HashMap::new();
+        for (index, number) in numbers.iter().enumerate() {
+            for position in number.border() {
+                if symbols.contains_key(&position) {
+                    neighbours.entry(position).or_default().push(index);
+                }
+            }
+        }
+        Schematic {
+            numbers,
+            symbols,
+            neighbours,
+        }
+    }
+
+    pub fn adjacent_symbols<'a>(&'a self, number: &Number) -> impl Iterator<Item = &'a Symbol> {
+        number
+            .border()
+            .filter_map(|position| self.symbols.get(&position))
+    }
+
+    pub fn part_numbers(&self) -> impl Iterator<Item = &Number> {
+        self.numbers
+            .iter()
+            .filter(|number| self.adjacent_symbols(number).next().is_some())
+    }
+
+    pub fn numbers_adjacent_to(&self, symbol: &Symbol) -> impl Iterator<Item = &Number> {
+        self.neighbours
+            .get(&symbol.position)
+            .into_iter()
+            .flatten()
+            .map(|&index| &self.numbers[index])
+    }
+
+    pub fn gears(
+        &self,
+        char: char,
+        neighbours: usize,
+    ) -> impl Iterator<Item = (&Symbol, Vec<&Number>)> {
+        self.symbols
+            .values()
+            .filter(move |symbol| symbol.char == char)
+            .map(|symbol| (symbol, self.numbers_adjacent_to(symbol).collect_vec()))
+            .filter(move |(_, numbers)| numbers.len() == neighbours)
+    }
+
+    pub fn gear_ratios(&self, char: char) -> u32 {
+        self.gears(char, 2)
+            .map(|(_, numbers)| numbers.iter().map(|number| number.value).product::<u32>())
+            .sum()
+    }
 }
 
 #[aoc(day3, part1)]
-fn part1(input: &[Element]) -> u32 {
-    let (numbers, symbols): (Vec<Number>, Vec<Symbol>) =
-        input.iter().partition_map(|element| match &element {
-            Element::Number(number) => Either::Left(number),
-            Element::Symbol(symbol) => Either::Right(symbol),
-        });
-
-    numbers
-        .iter()
-        .filter(|number| symbols.iter().any(|symbol| number.is_adjacent_to(symbol)))
+fn part1(drawing: &Drawing) -> u32 {
+    Schematic::new(&drawing.elements)
+        .part_numbers()
         .map(|number| number.value)
         .sum()
 }
 
 #[aoc(day3, part2)]
-fn part2(input: &[Element]) -> u32 {
-    let (numbers, symbols): (Vec<Number>, Vec<Symbol>) =
-        input.iter().partition_map(|element| match &element {
-            Element::Number(number) => Either::Left(number),
-            Element::Symbol(symbol) => Either::Right(symbol),
-        });
-
-    symbols
+fn part2(drawing: &Drawing) -> u32 {
+    Schematic::new(&drawing.elements).gear_ratios('*')
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RenderMode {
+    Ansi,
+    // Each row is followed by a row of markers
+    Plain,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Mark {
+    Empty,
+    PartNumber,
+    OtherNumber,
+    Gear,
+    Symbol,
+}
+
+impl Mark {
+    fn marker(self) -> char {
+        match self {
+            Mark::Empty => ' ',
+            Mark::PartNumber => 'P',
+            Mark::OtherNumber => 'n',
+            Mark::Gear => 'G',
+            Mark::Symbol => 's',
+        }
+    }
+
+    fn ansi_color(self) -> Option<&'static str> {
+        match self {
+            Mark::Empty | Mark::Symbol => None,
+            Mark::PartNumber => Some("\x1b[32m"),
+            Mark::OtherNumber => Some("\x1b[31m"),
+            Mark::Gear => Some("\x1b[1;33m"),
+        }
+    }
+}
+
+pub fn render(drawing: &Drawing, mode: RenderMode) -> String {
+    let schematic = Schematic::new(&drawing.elements);
+    let part_numbers: HashSet<_> = schematic
+        .part_numbers()
+        .map(|number| number.span.position)
+        .collect();
+    let gears: HashSet<_> = schematic
+        .gears('*', 2)
+        .map(|(symbol, _)| symbol.position)
+        .collect();
+
+    let mut grid = drawing
+        .lines
         .iter()
-        .filter(|&&Symbol { char, .. }| char == '*')
-        .filter_map(|symbol| {
-            let Some((a, b)) = numbers
+        .map(|line| line.chars().map(|char| (char, Mark::Empty)).collect_vec())
+        .collect_vec();
+    for element in &drawing.elements {
+        match element {
+            Element::Number(Number { span, .. }) => {
+                let mark = if part_numbers.contains(&span.position) {
+                    Mark::PartNumber
+                } else {
+                    Mark::OtherNumber
+                };
+                let Position { row, col } = span.position;
+                for (_, cell_mark) in &mut grid[row][col..col + span.length] {
+                    *cell_mark = mark;
+                }
+            }
+            Element::Symbol(Symbol { position, .. }) => {
+                grid[position.row][position.col].1 = if gears.contains(position) {
+                    Mark::Gear
+                } else {
+                    Mark::Symbol
+                };
+            }
+        }
+    }
+
+    grid.iter()
+        .map(|cells| match mode {
+            RenderMode::Ansi => cells
                 .iter()
-                .filter(|number| number.is_adjacent_to(symbol))
-                .collect_tuple()
-            else {
-                return None;
-            };
-            Some(a.value * b.value)
+                .map(|&(char, mark)| match mark.ansi_color() {
+                    Some(color) => format!("{color}{char}\x1b[0m"),
+                    None => char.to_string(),
+                })
+                .collect::<String>(),
+            RenderMode::Plain => {
+                let chars = cells.iter().map(|&(char, _)| char).collect::<String>();
+                let marks = cells
+                    .iter()
+                    .map(|&(_, mark)| mark.marker())
+                    .collect::<String>();
+                format!("{chars}\n{}", marks.trim_end())
+            }
         })
-        .sum()
+        .join("\n")
+}
+
+pub fn gear_report(drawing: &Drawing) -> String {
+    Schematic::new(&drawing.elements)
+        .gears('*', 2)
+        .map(|(symbol, numbers)| (symbol.position, numbers))
+        .sorted_by_key(|&(position, _)| position)
+        .map(|(Position { row, col }, numbers)| {
+            let ratio: u32 = numbers.iter().map(|number| number.value).product();
+            format!(
+                "* at row {row}, col {col}: {} = {ratio}",
+                numbers.iter().map(|number| number.value).join(" * ")
+            )
+        })
+        .join("\n")
 }
 
 mod parser {
@@ -173,4 +362,92 @@ mod tests {
     fn part2_example() {
         assert_eq!(part2(&parse(EXAMPLE).unwrap()), 467835);
     }
+
+    #[test]
+    fn schematic_queries() {
+        let schematic = Schematic::new(parse(EXAMPLE).unwrap().elements());
+        let symbol = |char| {
+            schematic
+                .gears(char, 1)
+                .map(|(symbol, _)| symbol)
+                .next()
+                .unwrap()
+        };
+        let values = |symbol| {
+            schematic
+                .numbers_adjacent_to(symbol)
+                .map(Number::value)
+                .sorted()
+                .collect_vec()
+        };
+        assert_eq!(values(symbol('#')), [633]);
+        assert_eq!(values(symbol('+')), [592]);
+        assert_eq!(values(symbol('$')), [664]);
+        assert_eq!(schematic.gears('*', 2).count(), 2);
+        assert_eq!(schematic.gears('*', 1).count(), 1);
+        assert_eq!(schematic.gears('#', 1).count(), 1);
+        assert_eq!(schematic.gear_ratios('#'), 0);
+    }
+
+    #[test]
+    fn part2_any_symbol() {
+        let schematic = Schematic::new(
+            &parse("12.....\n..#.3..\n.7..%..\n...4.5.")
+                .unwrap()
+                .elements,
+        );
+        assert_eq!(schematic.gear_ratios('#'), 12 * 7);
+        assert_eq!(schematic.gear_ratios('%'), 0);
+        assert_eq!(schematic.gears('%', 3).count(), 1);
+    }
+
+    #[test]
+    fn render_plain() {
+        assert_eq!(
+            render(&parse(EXAMPLE).unwrap(), RenderMode::Plain),
+            "467..114..
+PPP  nnn
+...*......
+   G
+..35..633.
+  PP  PPP
+......#...
+      s
+617*......
+PPPs
+.....+.58.
+     s nn
+..592.....
+  PPP
+......755.
+      PPP
+...$.*....
+   s G
+.664.598..
+ PPP PPP"
+        );
+        assert_eq!(
+            render(&parse("007*..\n......").unwrap(), RenderMode::Plain),
+            "007*..\nPPPs\n......\n"
+        );
+    }
+
+    #[test]
+    fn render_ansi() {
+        let rendered = render(&parse("467..114\n...*....").unwrap(), RenderMode::Ansi);
+        assert_eq!(
+            rendered,
+            "\x1b[32m4\x1b[0m\x1b[32m6\x1b[0m\x1b[32m7\x1b[0m..\
+             \x1b[31m1\x1b[0m\x1b[31m1\x1b[0m\x1b[31m4\x1b[0m\n...*...."
+        );
+    }
+
+    #[test]
+    fn gear_report_example() {
+        assert_eq!(
+            gear_report(&parse(EXAMPLE).unwrap()),
+            "* at row 1, col 3: 467 * 35 = 16345
+* at row 8, col 5: 755 * 598 = 451490"
+        );
+    }
 }
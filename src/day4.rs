@@ -1,6 +1,6 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use aoc_runner_derive::{aoc, aoc_generator};
-use std::collections::{HashSet, VecDeque};
+use std::collections::HashSet;
 
 struct Card<Numbers> {
     winning_numbers: Numbers,
@@ -28,43 +28,78 @@ fn part1(cards: &[Card<HashSet<u32>>]) -> u32 {
 }
 
 #[aoc(day4, part2)]
-fn part2(cards: &[Card<HashSet<u32>>]) -> u32 {
-    let (_, score) = cards
+fn part2(cards: &[Card<HashSet<u32>>]) -> u64 {
+    let matches: Vec<_> = cards
         .iter()
         .map(|card| card.winning_numbers.intersection(&card.my_numbers).count())
-        .fold(
-            (VecDeque::new(), 0),
-            |(mut stack, score), matching_numbers| {
-                let cards = stack.pop_front().unwrap_or(0) + 1;
-                for i in 0..matching_numbers {
-                    if let Some(c) = stack.get_mut(i) {
-                        *c += cards;
-                    } else {
-                        stack.push_back(cards);
-                    }
-                }
-                (stack, score + cards)
-            },
-        );
-    score
+        .collect();
+    card_copies(&matches, PastEnd::Ignore)
+        .unwrap()
+        .into_iter()
+        .sum()
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PastEnd {
+    Ignore,
+    Reject,
+}
+
+// Copies held of each card once every card has awarded its copies, originals included
+pub fn card_copies(matches: &[usize], past_end: PastEnd) -> Result<Vec<u64>> {
+    let mut copies = vec![1; matches.len()];
+    for (i, &matching_numbers) in matches.iter().enumerate() {
+        if past_end == PastEnd::Reject && i + matching_numbers >= matches.len() {
+            bail!(
+                "Card {} awards copies of {matching_numbers} cards but only {} follow it",
+                i + 1,
+                matches.len() - i - 1
+            );
+        }
+        let won = copies[i];
+        for c in copies.iter_mut().skip(i + 1).take(matching_numbers) {
+            *c += won;
+        }
+    }
+    Ok(copies)
+}
+
+// Sets the parser can build a card from, numbers they cannot hold are rejected while parsing
+trait NumberSet: FromIterator<u32> {
+    fn accepts(_number: u32) -> bool {
+        true
+    }
+}
+
+impl NumberSet for HashSet<u32> {}
+
 #[derive(Copy, Clone)]
-struct Bits(u128);
+struct Bits<const WORDS: usize = 2>([u64; WORDS]);
 
-impl FromIterator<u32> for Bits {
+impl<const WORDS: usize> FromIterator<u32> for Bits<WORDS> {
     fn from_iter<T: IntoIterator<Item = u32>>(iter: T) -> Self {
-        let mut bits = 0;
+        let mut words = [0; WORDS];
         for i in iter {
-            bits |= 1 << i;
+            assert!(Self::accepts(i), "{i} does not fit in {WORDS} words");
+            words[i as usize / 64] |= 1 << (i % 64);
         }
-        Bits(bits)
+        Bits(words)
+    }
+}
+
+impl<const WORDS: usize> NumberSet for Bits<WORDS> {
+    fn accepts(number: u32) -> bool {
+        (number as usize) < WORDS * u64::BITS as usize
     }
 }
 
-impl Bits {
+impl<const WORDS: usize> Bits<WORDS> {
     fn matching_bits(&self, other: &Self) -> u32 {
-        (self.0 & other.0).count_ones()
+        self.0
+            .iter()
+            .zip(&other.0)
+            .map(|(a, b)| (a & b).count_ones())
+            .sum()
     }
 }
 
@@ -90,48 +125,48 @@ fn part1_bits(cards: &[Card<Bits>]) -> u32 {
 }
 
 #[aoc(day4, part2, bits)]
-fn part2_bits(cards: &[Card<Bits>]) -> u32 {
-    let (_, score) = cards
+fn part2_bits(cards: &[Card<Bits>]) -> u64 {
+    let matches: Vec<_> = cards
         .iter()
-        .map(|card| card.winning_numbers.matching_bits(&card.my_numbers))
-        .fold(
-            (VecDeque::new(), 0),
-            |(mut stack, score), matching_numbers| {
-                let cards = stack.pop_front().unwrap_or(0) + 1;
-                for i in 0..matching_numbers as usize {
-                    if let Some(c) = stack.get_mut(i) {
-                        *c += cards;
-                    } else {
-                        stack.push_back(cards);
-                    }
-                }
-                (stack, score + cards)
-            },
-        );
-    score
+        .map(|card| card.winning_numbers.matching_bits(&card.my_numbers) as usize)
+        .collect();
+    card_copies(&matches, PastEnd::Ignore)
+        .unwrap()
+        .into_iter()
+        .sum()
 }
 
 mod parser {
-    use crate::day4::Card;
+    use crate::day4::{Card, NumberSet};
     use crate::nom_parser::number;
     use nom::bytes::complete::tag;
-    use nom::combinator::opt;
+    use nom::combinator::{all_consuming, opt};
+    use nom::error::{Error, ErrorKind};
     use nom::multi::{fold_many0, separated_list1};
-    use nom::sequence::preceded;
+    use nom::sequence::{preceded, terminated};
     use nom::IResult;
 
-    fn numbers(input: &str) -> IResult<&str, Vec<u32>> {
-        separated_list1(tag(" "), preceded(opt(tag(" ")), number))(input)
+    // A number the set cannot hold fails the whole parse rather than ending the list
+    fn accepted_number<T: NumberSet>(input: &str) -> IResult<&str, u32> {
+        let (rest, n) = number(input)?;
+        if !T::accepts(n) {
+            return Err(nom::Err::Failure(Error::new(input, ErrorKind::Verify)));
+        }
+        Ok((rest, n))
+    }
+
+    fn numbers<T: NumberSet>(input: &str) -> IResult<&str, Vec<u32>> {
+        separated_list1(tag(" "), preceded(opt(tag(" ")), accepted_number::<T>))(input)
     }
 
-    fn card<T: FromIterator<u32>>(input: &str) -> IResult<&str, Card<T>> {
+    fn card<T: NumberSet>(input: &str) -> IResult<&str, Card<T>> {
         let (input, _) = tag("Card ")(input)?;
         let (input, _) = fold_many0(tag(" "), || (), |acc, _| acc)(input)?;
         let (input, _) = number::<u32>(input)?;
         let (input, _) = tag(": ")(input)?;
-        let (input, winning_numbers) = numbers(input)?;
+        let (input, winning_numbers) = numbers::<T>(input)?;
         let (input, _) = tag(" | ")(input)?;
-        let (input, my_numbers) = numbers(input)?;
+        let (input, my_numbers) = numbers::<T>(input)?;
         Ok((
             input,
             Card {
@@ -141,8 +176,8 @@ mod parser {
         ))
     }
 
-    pub fn cards<T: FromIterator<u32>>(input: &str) -> IResult<&str, Vec<Card<T>>> {
-        separated_list1(tag("\n"), card)(input)
+    pub fn cards<T: NumberSet>(input: &str) -> IResult<&str, Vec<Card<T>>> {
+        all_consuming(terminated(separated_list1(tag("\n"), card), opt(tag("\n"))))(input)
     }
 }
 
@@ -166,4 +201,60 @@ Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
     fn part2_example() {
         assert_eq!(part2(&parse(EXAMPLE).unwrap()), 30);
     }
+
+    #[test]
+    fn bits_match_hash_sets() {
+        let cards = parse(EXAMPLE).unwrap();
+        let bits = parse_bits(EXAMPLE).unwrap();
+        assert_eq!(part1_bits(&bits), part1(&cards));
+        assert_eq!(part2_bits(&bits), part2(&cards));
+    }
+
+    #[test]
+    fn numbers_past_bit_capacity() {
+        const LARGE: &str = "Card 1: 41 130 200 | 200 130 6 63 64 41";
+        assert!(parse_bits(LARGE).is_err());
+
+        let (_, cards) = parser::cards::<Bits<4>>(LARGE).unwrap();
+        assert_eq!(
+            cards[0].winning_numbers.matching_bits(&cards[0].my_numbers),
+            3
+        );
+        let (_, cards) = parser::cards::<Bits<4>>("Card 1: 1 255 | 255 1").unwrap();
+        assert_eq!(
+            cards[0].winning_numbers.matching_bits(&cards[0].my_numbers),
+            2
+        );
+        assert!(parser::cards::<Bits<4>>("Card 1: 1 256 | 256 1").is_err());
+    }
+
+    #[test]
+    fn rejects_leftover_input() {
+        const LARGE_THEN_CARD: &str = "Card 1: 41 48 | 41 200 48\nCard 2: 1 2 | 1 2";
+        assert!(parse_bits(LARGE_THEN_CARD).is_err());
+        assert!(matches!(
+            parser::cards::<Bits>(LARGE_THEN_CARD),
+            Err(nom::Err::Failure(_))
+        ));
+        assert!(parse("Card 1: 41 48 | 41 48\nCard 2: 1 2 |").is_err());
+        assert_eq!(parse_bits("Card 1: 41 48 | 41 48\n").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn copies_per_card() {
+        assert_eq!(
+            card_copies(&[4, 2, 2, 1, 0, 0], PastEnd::Reject).unwrap(),
+            vec![1, 2, 4, 8, 14, 1]
+        );
+        assert_eq!(
+            card_copies(&[1, 3, 0], PastEnd::Ignore).unwrap(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            card_copies(&[1, 3, 0], PastEnd::Reject)
+                .unwrap_err()
+                .to_string(),
+            "Card 2 awards copies of 3 cards but only 1 follow it"
+        );
+    }
 }
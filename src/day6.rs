@@ -1,100 +1,112 @@
+use crate::nom_parser::to_result;
 use anyhow::Result;
 use aoc_runner_derive::{aoc, aoc_generator};
+use num::integer::Roots;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Race<T> {
+    time: T,
+    record: T,
+}
+
+impl<T> Race<T> {
+    fn new(time: T, record: T) -> Self {
+        Race { time, record }
+    }
+}
 
 struct Races {
-    times: Vec<u32>,
-    distances: Vec<u32>,
+    races: Vec<Race<u32>>,
+    race_with_bad_kerning: Race<u64>,
 }
 
-#[aoc_generator(day6, part1)]
+// At most 64 bits wide, so that `time * time` cannot overflow the u128 intermediates
+trait RaceValue: Copy + Into<u128> + TryFrom<u128> {}
+
+impl RaceValue for u8 {}
+impl RaceValue for u16 {}
+impl RaceValue for u32 {}
+impl RaceValue for u64 {}
+
+impl<T: RaceValue> Race<T> {
+    // Counts the holding times `h` in `0..=time` such that `h * (time - h) > record`
+    fn ways_to_win(&self) -> T {
+        let (time, record): (u128, u128) = (self.time.into(), self.record.into());
+        let distance = |hold: u128| hold * (time - hold);
+
+        let ways = match (time * time).checked_sub(4 * record) {
+            None | Some(0) => 0,
+            Some(delta) => {
+                // Approximate lower root, then nudge it until it is the first winning hold
+                let mut lower = (time - delta.sqrt()) / 2;
+                while lower > 0 && distance(lower - 1) > record {
+                    lower -= 1;
+                }
+                while lower <= time / 2 && distance(lower) <= record {
+                    lower += 1;
+                }
+                (time + 1).saturating_sub(2 * lower)
+            }
+        };
+        match T::try_from(ways) {
+            Ok(ways) => ways,
+            Err(_) => unreachable!("There are fewer ways to win than holding times"),
+        }
+    }
+}
+
+#[aoc_generator(day6)]
 fn parse(input: &str) -> Result<Races> {
-    let (_, races) = parser::races(input).map_err(|err| {
-        err.map(|error| nom::error::Error::new(error.input.to_string(), error.code))
-    })?;
-    Ok(races)
+    to_result(parser::races(input))
 }
 
 #[aoc(day6, part1)]
 fn part1(races: &Races) -> u32 {
-    races
-        .times
-        .iter()
-        .zip(&races.distances)
-        .map(|(&time, &distance)| {
-            let delta: f64 = (time * time - 4 * distance).into();
-            let time_f64: f64 = time.into();
-            let sqrt = delta.sqrt();
-            let root1 = (time_f64 - sqrt) / 2.0;
-            let root2 = (time_f64 + sqrt) / 2.0;
-            root2.ceil() as u32 - root1.floor() as u32 - 1
-        })
-        .product()
-}
-
-#[aoc_generator(day6, part2)]
-fn parse_with_bad_kerning(input: &str) -> Result<(u64, u64)> {
-    let (_, race) = parser::race_with_bad_kerning(input).map_err(|err| {
-        err.map(|error| nom::error::Error::new(error.input.to_string(), error.code))
-    })?;
-    Ok(race)
+    races.races.iter().map(Race::ways_to_win).product()
 }
 
 #[aoc(day6, part2)]
-fn part2(&(time, distance): &(u64, u64)) -> u64 {
-    let delta: f64 = (time * time - 4 * distance) as f64;
-    let time_f64 = time as f64;
-    let sqrt = delta.sqrt();
-    let root1 = (time_f64 - sqrt) / 2.0;
-    let root2 = (time_f64 + sqrt) / 2.0;
-    root2.ceil() as u64 - root1.floor() as u64 - 1
+fn part2(races: &Races) -> u64 {
+    races.race_with_bad_kerning.ways_to_win()
 }
 
 mod parser {
-    use crate::day6::Races;
-    use crate::nom_parser::number;
-    use nom::bytes::complete::{tag, take_while1};
-    use nom::combinator::opt;
-    use nom::error::ErrorKind;
-    use nom::multi::{fold_many1, many1, separated_list1};
-    use nom::sequence::preceded;
+    use crate::day6::{Race, Races};
+    use crate::nom_parser::{number, number_with_spaces};
+    use nom::bytes::complete::tag;
+    use nom::character::complete::{newline, space1};
+    use nom::combinator::consumed;
+    use nom::multi::separated_list1;
+    use nom::sequence::{pair, preceded, separated_pair};
     use nom::IResult;
-    use std::str::FromStr;
 
-    fn spaces(input: &str) -> IResult<&str, ()> {
-        fold_many1(tag(" "), || (), |acc, _| acc)(input)
+    // Reads the values both column by column and as a single number with bad kerning
+    fn values<'a>(label: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, (Vec<u32>, u64)> {
+        move |input| {
+            let (input, (columns, values)) = preceded(
+                pair(tag(label), space1),
+                consumed(separated_list1(space1, number)),
+            )(input)?;
+            let (_, merged) = number_with_spaces(columns)?;
+            Ok((input, (values, merged)))
+        }
     }
 
     pub fn races(input: &str) -> IResult<&str, Races> {
-        let (input, _) = preceded(tag("Time:"), spaces)(input)?;
-        let (input, times) = separated_list1(spaces, number)(input)?;
-        let (input, _) = preceded(tag("\nDistance:"), spaces)(input)?;
-        let (input, distances) = separated_list1(spaces, number)(input)?;
-
-        Ok((input, Races { times, distances }))
-    }
-
-    pub fn number_with_bad_kerning<T: FromStr>(input: &str) -> IResult<&str, T> {
-        let (input, number_str) = many1(preceded(
-            opt(spaces),
-            take_while1(|c: char| c.is_ascii_digit()),
-        ))(input)?;
+        let (input, ((times, time), (records, record))) =
+            separated_pair(values("Time:"), newline, values("Distance:"))(input)?;
         Ok((
             input,
-            number_str
-                .join("")
-                .parse()
-                .map_err(|_| nom::Err::Failure(nom::error::Error::new(input, ErrorKind::Fail)))?,
+            Races {
+                races: times
+                    .into_iter()
+                    .zip(records)
+                    .map(|(time, record)| Race::new(time, record))
+                    .collect(),
+                race_with_bad_kerning: Race::new(time, record),
+            },
         ))
     }
-
-    pub fn race_with_bad_kerning(input: &str) -> IResult<&str, (u64, u64)> {
-        let (input, _) = preceded(tag("Time:"), spaces)(input)?;
-        let (input, time) = number_with_bad_kerning(input)?;
-        let (input, _) = preceded(tag("\nDistance:"), spaces)(input)?;
-        let (input, distance) = number_with_bad_kerning(input)?;
-
-        Ok((input, (time, distance)))
-    }
 }
 
 #[cfg(test)]
@@ -111,6 +123,51 @@ Distance:  9  40  200";
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse_with_bad_kerning(EXAMPLE).unwrap()), 71503);
+        assert_eq!(part2(&parse(EXAMPLE).unwrap()), 71503);
+    }
+
+    #[test]
+    fn exact_ties() {
+        assert_eq!(Race::new(7u32, 9).ways_to_win(), 4);
+        assert_eq!(Race::new(15u32, 40).ways_to_win(), 8);
+        // Perfect square discriminant: holding 10 or 20 only ties the record
+        assert_eq!(Race::new(30u32, 200).ways_to_win(), 9);
+        assert_eq!(Race::new(4u32, 4).ways_to_win(), 0);
+        assert_eq!(Race::new(4u32, 3).ways_to_win(), 1);
+        assert_eq!(Race::new(2u32, 0).ways_to_win(), 1);
+        assert_eq!(Race::new(0u32, 0).ways_to_win(), 0);
+        assert_eq!(Race::new(10u8, 100).ways_to_win(), 0);
+    }
+
+    #[test]
+    fn no_overflow() {
+        assert_eq!(Race::new(65_536u32, 0).ways_to_win(), 65_535);
+        assert_eq!(Race::new(u32::MAX, 0).ways_to_win(), u32::MAX - 1);
+        assert_eq!(Race::new(u64::MAX, 0).ways_to_win(), u64::MAX - 1);
+        // Beyond f64 precision, only the middle hold beats the record
+        assert_eq!(
+            Race::new(2_000_000_000u64, 999_999_999_999_999_999).ways_to_win(),
+            1
+        );
+        assert_eq!(
+            Race::new(200_000_002u64, 10_000_000_200_000_000).ways_to_win(),
+            1
+        );
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        for time in 0u32..60 {
+            for record in 0..time * time / 4 + 2 {
+                let expected = (0..=time)
+                    .filter(|hold| hold * (time - hold) > record)
+                    .count() as u32;
+                assert_eq!(
+                    Race::new(time, record).ways_to_win(),
+                    expected,
+                    "{time} {record}"
+                );
+            }
+        }
     }
 }
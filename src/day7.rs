@@ -2,7 +2,6 @@ use anyhow::Result;
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
 use std::cmp::Ordering;
-use std::mem;
 
 struct Hand {
     cards: Vec<Card>,
@@ -29,15 +28,6 @@ impl Card {
             Card::A => 12,
         }
     }
-
-    fn cmp_with_joker(&self, other: &Self) -> Ordering {
-        match (self, other) {
-            (Card::J, Card::J) => Ordering::Equal,
-            (Card::J, _) => Ordering::Less,
-            (_, Card::J) => Ordering::Greater,
-            _ => self.cmp(other),
-        }
-    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
@@ -51,44 +41,132 @@ enum HandType {
     FiveOfAKind,
 }
 
-impl Hand {
-    fn hand_type(&self) -> HandType {
-        let mut cards_count = [0u8; 13];
-        for &card in &self.cards {
-            cards_count[card.into_usize()] += 1;
-        }
-        cards_count.sort_by(|a, b| a.cmp(b).reverse());
-        match cards_count.as_slice() {
-            [5, ..] => HandType::FiveOfAKind,
-            [4, ..] => HandType::FourOfAKind,
-            [3, 2, ..] => HandType::FullHouse,
-            [3, ..] => HandType::ThreeOfAKind,
-            [2, 2, ..] => HandType::TwoPair,
-            [2, ..] => HandType::OnePair,
-            [1, ..] => HandType::HighCard,
-            _ => unreachable!(),
-        }
+// Everything needed to rank a hand, accumulated one card at a time
+#[derive(Debug, Default, Copy, Clone)]
+struct Tally {
+    cards_count: [u8; 13],
+    wildcards: u8,
+    // One nibble per card strength
+    strengths: u32,
+}
+
+struct Rules<'a> {
+    // From the weakest to the strongest card
+    ranking: &'a [Card],
+    wildcards: &'a [Card],
+}
+
+const STANDARD_RULES: Rules = Rules {
+    ranking: &[
+        Card::N(2),
+        Card::N(3),
+        Card::N(4),
+        Card::N(5),
+        Card::N(6),
+        Card::N(7),
+        Card::N(8),
+        Card::N(9),
+        Card::T,
+        Card::J,
+        Card::Q,
+        Card::K,
+        Card::A,
+    ],
+    wildcards: &[],
+};
+
+const JOKER_RULES: Rules = Rules {
+    ranking: &[
+        Card::J,
+        Card::N(2),
+        Card::N(3),
+        Card::N(4),
+        Card::N(5),
+        Card::N(6),
+        Card::N(7),
+        Card::N(8),
+        Card::N(9),
+        Card::T,
+        Card::Q,
+        Card::K,
+        Card::A,
+    ],
+    wildcards: &[Card::J],
+};
+
+impl Rules<'_> {
+    fn strength(&self, card: Card) -> usize {
+        self.ranking
+            .iter()
+            .position(|&c| c == card)
+            .expect("Card missing from ranking")
     }
 
-    fn hand_type_with_jokers(&self) -> HandType {
-        let mut cards_count = [0u8; 13];
-        for &card in &self.cards {
-            cards_count[card.into_usize()] += 1;
+    fn tally(&self, mut tally: Tally, card: Card) -> Tally {
+        if self.wildcards.contains(&card) {
+            tally.wildcards += 1;
+        } else {
+            tally.cards_count[card.into_usize()] += 1;
         }
-        let jokers = mem::replace(&mut cards_count[Card::J.into_usize()], 0);
+        tally.strengths = tally.strengths << 4 | self.strength(card) as u32;
+        tally
+    }
+
+    fn tally_hand(&self, hand: &Hand) -> Tally {
+        hand.cards
+            .iter()
+            .fold(Tally::default(), |tally, &card| self.tally(tally, card))
+    }
+
+    fn hand_type(&self, hand: &Hand) -> HandType {
+        Self::tally_type(self.tally_hand(hand))
+    }
+
+    fn tally_type(
+        Tally {
+            mut cards_count,
+            wildcards,
+            ..
+        }: Tally,
+    ) -> HandType {
         cards_count.sort_by(|a, b| a.cmp(b).reverse());
-        cards_count[0] += jokers;
-        match cards_count.as_slice() {
-            [5, ..] => HandType::FiveOfAKind,
-            [4, ..] => HandType::FourOfAKind,
-            [3, 2, ..] => HandType::FullHouse,
-            [3, ..] => HandType::ThreeOfAKind,
-            [2, 2, ..] => HandType::TwoPair,
-            [2, ..] => HandType::OnePair,
-            [1, ..] => HandType::HighCard,
-            _ => unreachable!(),
+        cards_count[0] += wildcards;
+        match (cards_count[0], cards_count[1]) {
+            (5.., _) => HandType::FiveOfAKind,
+            (4, _) => HandType::FourOfAKind,
+            (3, 2..) => HandType::FullHouse,
+            (3, _) => HandType::ThreeOfAKind,
+            (2, 2) => HandType::TwoPair,
+            (2, _) => HandType::OnePair,
+            _ => HandType::HighCard,
         }
     }
+
+    fn cmp_cards(&self, a: &Hand, b: &Hand) -> Ordering {
+        a.cards
+            .iter()
+            .map(|&card| self.strength(card))
+            .cmp(b.cards.iter().map(|&card| self.strength(card)))
+    }
+
+    // Hand type in the high bits, then one nibble per card so that an integer sort ranks hands
+    fn pack_tally(tally: Tally) -> u32 {
+        (Self::tally_type(tally) as u32) << 20 | tally.strengths
+    }
+
+    fn total_winnings(&self, hands_and_bids: &[(Hand, u32)]) -> u32 {
+        hands_and_bids
+            .iter()
+            .map(|(hand, bid)| (hand, bid, self.hand_type(hand)))
+            .sorted_by(|(hand_a, _, hand_a_type), (hand_b, _, hand_b_type)| {
+                hand_a_type
+                    .cmp(hand_b_type)
+                    .then_with(|| self.cmp_cards(hand_a, hand_b))
+            })
+            .enumerate()
+            .map(|(i, (_, &bid, _))| (i as u32 + 1) * bid)
+            .sum()
+    }
 }
 
 #[aoc_generator(day7)]
@@ -101,48 +179,60 @@ fn parse(input: &str) -> Result<Vec<(Hand, u32)>> {
 
 #[aoc(day7, part1)]
 fn part1(hands_and_bids: &[(Hand, u32)]) -> u32 {
-    hands_and_bids
-        .iter()
-        .map(|(hand, bid)| (hand, bid, hand.hand_type()))
-        .sorted_by(|(hand_a, _, hand_a_type), (hand_b, _, hand_b_type)| {
-            hand_a_type
-                .cmp(hand_b_type)
-                .then_with(|| hand_a.cards.cmp(&hand_b.cards))
-        })
-        .enumerate()
-        .map(|(i, (_, &bid, _))| (i as u32 + 1) * bid)
-        .sum()
+    STANDARD_RULES.total_winnings(hands_and_bids)
 }
 
 #[aoc(day7, part2)]
 fn part2(hands_and_bids: &[(Hand, u32)]) -> u32 {
-    hands_and_bids
+    JOKER_RULES.total_winnings(hands_and_bids)
+}
+
+// Cards are tallied while they are parsed, no `Hand` is built
+fn parse_packed(input: &str, rules: &Rules) -> Result<Vec<(u32, u32)>> {
+    let (_, keys_and_bids) = parser::packed_keys_and_bids(input, rules).map_err(|err| {
+        err.map(|error| nom::error::Error::new(error.input.to_string(), error.code))
+    })?;
+    Ok(keys_and_bids)
+}
+
+fn total_winnings_packed(keys_and_bids: &[(u32, u32)]) -> u32 {
+    let mut keys_and_bids = keys_and_bids.to_vec();
+    keys_and_bids.sort_unstable();
+    keys_and_bids
         .iter()
-        .map(|(hand, bid)| (hand, bid, hand.hand_type_with_jokers()))
-        .sorted_by(|(hand_a, _, hand_a_type), (hand_b, _, hand_b_type)| {
-            hand_a_type.cmp(hand_b_type).then_with(|| {
-                hand_a
-                    .cards
-                    .iter()
-                    .zip(&hand_b.cards)
-                    .map(|(a, b)| a.cmp_with_joker(b))
-                    .find(|ord| ord.is_ne())
-                    .unwrap_or(Ordering::Equal)
-            })
-        })
         .enumerate()
-        .map(|(i, (_, &bid, _))| (i as u32 + 1) * bid)
+        .map(|(i, (_, bid))| (i as u32 + 1) * bid)
         .sum()
 }
 
+#[aoc_generator(day7, part1, packed)]
+fn parse_packed_part1(input: &str) -> Result<Vec<(u32, u32)>> {
+    parse_packed(input, &STANDARD_RULES)
+}
+
+#[aoc(day7, part1, packed)]
+fn part1_packed(keys_and_bids: &[(u32, u32)]) -> u32 {
+    total_winnings_packed(keys_and_bids)
+}
+
+#[aoc_generator(day7, part2, packed)]
+fn parse_packed_part2(input: &str) -> Result<Vec<(u32, u32)>> {
+    parse_packed(input, &JOKER_RULES)
+}
+
+#[aoc(day7, part2, packed)]
+fn part2_packed(keys_and_bids: &[(u32, u32)]) -> u32 {
+    total_winnings_packed(keys_and_bids)
+}
+
 mod parser {
-    use crate::day7::{Card, Hand};
+    use crate::day7::{Card, Hand, Rules, Tally};
     use crate::nom_parser::number;
     use nom::branch::alt;
     use nom::bytes::complete::{tag, take};
     use nom::character::complete::{line_ending, u8};
     use nom::combinator::{map, map_parser};
-    use nom::multi::{many_m_n, separated_list1};
+    use nom::multi::{fold_many_m_n, many_m_n, separated_list1};
     use nom::sequence::{pair, preceded};
     use nom::IResult;
 
@@ -166,6 +256,24 @@ mod parser {
             ),
         )(input)
     }
+
+    pub fn packed_keys_and_bids<'a>(
+        input: &'a str,
+        rules: &Rules,
+    ) -> IResult<&'a str, Vec<(u32, u32)>> {
+        separated_list1(
+            line_ending,
+            pair(
+                map(
+                    fold_many_m_n(5, 5, card, Tally::default, |tally, card| {
+                        rules.tally(tally, card)
+                    }),
+                    Rules::pack_tally,
+                ),
+                preceded(tag(" "), number),
+            ),
+        )(input)
+    }
 }
 
 #[cfg(test)]
@@ -181,10 +289,98 @@ QQQJA 483";
     #[test]
     fn part1_example() {
         assert_eq!(part1(&parse(EXAMPLE).unwrap()), 6440);
+        assert_eq!(part1_packed(&parse_packed_part1(EXAMPLE).unwrap()), 6440);
     }
 
     #[test]
     fn part2_example() {
         assert_eq!(part2(&parse(EXAMPLE).unwrap()), 5905);
+        assert_eq!(part2_packed(&parse_packed_part2(EXAMPLE).unwrap()), 5905);
+    }
+
+    fn hand(cards: &str) -> Hand {
+        let (hand, _) = parse(&format!("{cards} 0")).unwrap().pop().unwrap();
+        hand
+    }
+
+    #[test]
+    fn multiple_wildcards() {
+        let rules = Rules {
+            ranking: JOKER_RULES.ranking,
+            wildcards: &[Card::J, Card::N(2)],
+        };
+        assert_eq!(rules.hand_type(&hand("32T3K")), HandType::ThreeOfAKind);
+        assert_eq!(rules.hand_type(&hand("2JJ2A")), HandType::FiveOfAKind);
+        assert_eq!(rules.hand_type(&hand("2345J")), HandType::ThreeOfAKind);
+        assert_eq!(JOKER_RULES.hand_type(&hand("32T3K")), HandType::OnePair);
+    }
+
+    #[test]
+    fn different_wild_rank() {
+        let rules = Rules {
+            ranking: &[
+                Card::T,
+                Card::N(2),
+                Card::N(3),
+                Card::N(4),
+                Card::N(5),
+                Card::N(6),
+                Card::N(7),
+                Card::N(8),
+                Card::N(9),
+                Card::J,
+                Card::Q,
+                Card::K,
+                Card::A,
+            ],
+            wildcards: &[Card::T],
+        };
+        assert_eq!(rules.hand_type(&hand("T55J5")), HandType::FourOfAKind);
+        assert_eq!(rules.hand_type(&hand("KTJJT")), HandType::FourOfAKind);
+        assert_eq!(rules.hand_type(&hand("QQQJA")), HandType::ThreeOfAKind);
+        assert_eq!(
+            rules.cmp_cards(&hand("T2345"), &hand("23456")),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn hand_size_independent() {
+        assert_eq!(
+            STANDARD_RULES.hand_type(&Hand {
+                cards: vec![Card::A; 3]
+            }),
+            HandType::ThreeOfAKind
+        );
+        assert_eq!(
+            STANDARD_RULES.hand_type(&Hand {
+                cards: vec![Card::A; 7]
+            }),
+            HandType::FiveOfAKind
+        );
+        assert_eq!(
+            JOKER_RULES.hand_type(&Hand {
+                cards: vec![Card::J, Card::K]
+            }),
+            HandType::OnePair
+        );
+    }
+
+    #[test]
+    fn packed_keys_follow_rules_ordering() {
+        let hands = [
+            "32T3K", "T55J5", "KK677", "KTJJT", "QQQJA", "JJJJJ", "AAAAA", "23456",
+        ]
+        .map(hand);
+        for rules in [STANDARD_RULES, JOKER_RULES] {
+            for (a, b) in hands.iter().tuple_combinations() {
+                let expected = rules
+                    .hand_type(a)
+                    .cmp(&rules.hand_type(b))
+                    .then_with(|| rules.cmp_cards(a, b));
+                let pack = |hand| Rules::pack_tally(rules.tally_hand(hand));
+                assert_eq!(pack(a).cmp(&pack(b)), expected);
+            }
+        }
     }
 }
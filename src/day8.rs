@@ -1,11 +1,14 @@
-use anyhow::Result;
+use crate::cycle::{self, Cycle};
+use anyhow::{anyhow, Result};
 use aoc_runner_derive::{aoc, aoc_generator};
-use num::integer::lcm;
+use itertools::Itertools;
+use num::integer::{lcm, ExtendedGcd, Integer};
 use std::collections::HashMap;
-use std::fmt::{Debug, Formatter};
+use std::fmt::{Debug, Display, Formatter, Write};
+use std::str::FromStr;
 
 #[derive(Copy, Clone, Debug)]
-enum Direction {
+pub enum Direction {
     Left,
     Right,
 }
@@ -20,7 +23,7 @@ impl Direction {
 }
 
 #[derive(Copy, Clone, Hash, Eq, PartialEq)]
-struct Node([u8; 3]);
+pub struct Node([u8; 3]);
 
 impl Debug for Node {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -30,14 +33,108 @@ impl Debug for Node {
     }
 }
 
+impl FromStr for Node {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        s.as_bytes()
+            .try_into()
+            .map(Node)
+            .map_err(|_| anyhow!("Nodes are three bytes long: [{s}]"))
+    }
+}
+
+impl Display for Node {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.0))
+    }
+}
+
+impl Node {
+    pub fn is_start(&self) -> bool {
+        matches!(self, Node([_, _, b'A']))
+    }
+
+    pub fn is_end(&self) -> bool {
+        matches!(self, Node([_, _, b'Z']))
+    }
+
+    // Base 36 over digits and uppercase letters, so every node fits below `NODE_CODES`
+    fn code(&self) -> Option<usize> {
+        self.0.iter().try_fold(0, |code, &byte| {
+            let digit = match byte {
+                b'0'..=b'9' => byte - b'0',
+                b'A'..=b'Z' => byte - b'A' + 10,
+                _ => return None,
+            };
+            Some(code * 36 + usize::from(digit))
+        })
+    }
+}
+
+const NODE_CODES: usize = 36 * 36 * 36;
+
 const AAA: Node = Node([b'A', b'A', b'A']);
 const ZZZ: Node = Node([b'Z', b'Z', b'Z']);
 
-struct Map {
+pub struct Map {
     directions: Vec<Direction>,
     nodes: Vec<(Node, [Node; 2])>,
 }
 
+impl FromStr for Map {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse(s)
+    }
+}
+
+impl Map {
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph network {\n");
+        for (node, _) in &self.nodes {
+            if node.is_start() {
+                writeln!(dot, "    \"{node}\" [style=filled, fillcolor=green];").unwrap();
+            } else if node.is_end() {
+                writeln!(dot, "    \"{node}\" [style=filled, fillcolor=red];").unwrap();
+            }
+        }
+        for (node, [left, right]) in &self.nodes {
+            writeln!(dot, "    \"{node}\" -> \"{left}\" [label=\"L\"];").unwrap();
+            writeln!(dot, "    \"{node}\" -> \"{right}\" [label=\"R\"];").unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn cycle_summary(&self) -> String {
+        let nodes: HashMap<_, _> = self.nodes.iter().copied().collect();
+        let mut summary = String::new();
+        for (start, _) in self.nodes.iter().filter(|(node, _)| node.is_start()) {
+            let cycle = GhostCycle::detect(&self.directions, &nodes, *start);
+            let first_end = cycle
+                .prefix_hits
+                .first()
+                .copied()
+                .or_else(|| cycle.cycle_hits.first().map(|hit| cycle.cycle_start + hit));
+            write!(summary, "{start}: first Z ").unwrap();
+            match first_end {
+                Some(steps) => write!(summary, "after {steps} steps"),
+                None => write!(summary, "never reached"),
+            }
+            .unwrap();
+            writeln!(
+                summary,
+                ", cycle of {} steps from step {}, Z offsets {:?}",
+                cycle.cycle_length, cycle.cycle_start, cycle.cycle_hits
+            )
+            .unwrap();
+        }
+        summary
+    }
+}
+
 #[aoc_generator(day8)]
 fn parse(input: &str) -> Result<Map> {
     let (_, map) = parser::map_input(input).map_err(|err| {
@@ -60,24 +157,326 @@ fn part1(map: &Map) -> usize {
     unreachable!()
 }
 
+#[derive(Debug)]
+struct GhostCycle {
+    prefix_hits: Vec<usize>,
+    cycle_start: usize,
+    cycle_length: usize,
+    // Offsets of the Z nodes from the start of the cycle
+    cycle_hits: Vec<usize>,
+}
+
+impl GhostCycle {
+    fn detect(directions: &[Direction], nodes: &HashMap<Node, [Node; 2]>, start: Node) -> Self {
+        let states = cycle::hashed((start, 0), |&(node, index)| {
+            (
+                nodes[&node][directions[index].into_usize()],
+                (index + 1) % directions.len(),
+            )
+        });
+        let Cycle {
+            prefix_length: cycle_start,
+            cycle_length,
+        } = states.cycle;
+        let (prefix_hits, cycle_hits): (Vec<_>, Vec<_>) = states
+            .states()
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, (node, _))| node.is_end())
+            .map(|(step, _)| step)
+            .partition(|&hit| hit < cycle_start);
+        GhostCycle {
+            prefix_hits,
+            cycle_start,
+            cycle_length,
+            cycle_hits: cycle_hits
+                .into_iter()
+                .map(|hit| hit - cycle_start)
+                .collect(),
+        }
+    }
+
+    // Period `p` when the ghost stands on a Z node exactly at the multiples of `p`
+    fn period(&self) -> Option<usize> {
+        let hits = self.prefix_hits.iter().copied().chain(
+            self.cycle_hits
+                .iter()
+                .map(|offset| self.cycle_start + offset),
+        );
+        let period = hits.clone().next()?;
+        let end = self.cycle_start + self.cycle_length;
+        (self.cycle_length.is_multiple_of(period)
+            && hits.eq((1..).map(|k| k * period).take_while(|&step| step < end)))
+        .then_some(period)
+    }
+
+    fn is_end_at(&self, step: usize) -> bool {
+        if step < self.cycle_start {
+            self.prefix_hits.binary_search(&step).is_ok()
+        } else {
+            self.cycle_hits
+                .binary_search(&((step - self.cycle_start) % self.cycle_length))
+                .is_ok()
+        }
+    }
+}
+
+// Merges `t ≡ r1 (mod m1)` and `t ≡ r2 (mod m2)`, moduli do not need to be coprime
+fn crt((r1, m1): (i128, i128), (r2, m2): (i128, i128)) -> Option<(i128, i128)> {
+    let ExtendedGcd { gcd, x, .. } = m1.extended_gcd(&m2);
+    if (r2 - r1) % gcd != 0 {
+        return None;
+    }
+    let modulus = m1 / gcd * m2;
+    let k = ((r2 - r1) / gcd * x).rem_euclid(m2 / gcd);
+    Some(((r1 + m1 * k).rem_euclid(modulus), modulus))
+}
+
+const MAX_CRT_COMBINATIONS: usize = 1 << 16;
+
+fn first_common_step(cycles: &[GhostCycle]) -> Result<usize> {
+    let settled = cycles
+        .iter()
+        .map(|cycle| cycle.cycle_start)
+        .max()
+        .ok_or_else(|| anyhow!("No ghost to follow"))?;
+
+    // Before every ghost entered its cycle, the common step has to be a prefix hit
+    if let Some(step) = cycles
+        .iter()
+        .flat_map(|cycle| cycle.prefix_hits.iter().copied())
+        .filter(|&step| step < settled)
+        .sorted()
+        .find(|&step| cycles.iter().all(|cycle| cycle.is_end_at(step)))
+    {
+        return Ok(step);
+    }
+
+    let combinations = cycles
+        .iter()
+        .map(|cycle| cycle.cycle_hits.len())
+        .fold(1usize, |acc, len| acc.saturating_mul(len));
+    let step = if combinations > MAX_CRT_COMBINATIONS {
+        simulate(cycles, settled)
+    } else {
+        cycles
+            .iter()
+            .map(|cycle| {
+                let modulus = cycle.cycle_length as i128;
+                cycle
+                    .cycle_hits
+                    .iter()
+                    .map(move |&offset| ((cycle.cycle_start + offset) as i128 % modulus, modulus))
+            })
+            .multi_cartesian_product()
+            .filter_map(|congruences| congruences.into_iter().try_fold((0, 1), crt))
+            .map(|(remainder, modulus)| {
+                let (remainder, modulus) = (remainder as usize, modulus as usize);
+                remainder + settled.saturating_sub(remainder).div_ceil(modulus) * modulus
+            })
+            .min()
+    };
+    step.ok_or_else(|| anyhow!("The ghosts never stand on Z nodes at the same step"))
+}
+
+fn simulate(cycles: &[GhostCycle], settled: usize) -> Option<usize> {
+    let period = cycles.iter().map(|cycle| cycle.cycle_length).fold(1, lcm);
+    let pivot = cycles.iter().max_by_key(|cycle| cycle.cycle_length)?;
+    (0..=(settled + period) / pivot.cycle_length)
+        .flat_map(|k| {
+            pivot
+                .cycle_hits
+                .iter()
+                .map(move |&offset| pivot.cycle_start + offset + k * pivot.cycle_length)
+        })
+        .skip_while(|&step| step < settled)
+        .take_while(|&step| step < settled + period)
+        .find(|&step| cycles.iter().all(|cycle| cycle.is_end_at(step)))
+}
+
 #[aoc(day8, part2)]
-fn part2(map: &Map) -> usize {
+fn part2(map: &Map) -> Result<usize> {
+    let nodes: HashMap<_, _> = map.nodes.iter().copied().collect();
+
+    let cycles = nodes
+        .keys()
+        .filter(|node| node.is_start())
+        .map(|&start| GhostCycle::detect(&map.directions, &nodes, start))
+        .collect_vec();
+    first_common_step(&cycles)
+}
+
+// The usual shortcut, only valid once every ghost is checked to hit Z nodes at a fixed period
+#[aoc(day8, part2, lcm)]
+fn part2_lcm(map: &Map) -> Result<usize> {
     let nodes: HashMap<_, _> = map.nodes.iter().copied().collect();
 
     nodes
         .keys()
-        .filter(|node| matches!(node, Node([_, _, b'A'])))
-        .copied()
-        .map(|mut current_node| {
-            for (i, direction) in map.directions.iter().cycle().enumerate() {
-                current_node = nodes[&current_node][direction.into_usize()];
-                if matches!(current_node, Node([_, _, b'Z'])) {
-                    return i + 1;
+        .filter(|node| node.is_start())
+        .map(|&start| {
+            GhostCycle::detect(&map.directions, &nodes, start)
+                .period()
+                .ok_or_else(|| anyhow!("{start} does not reach Z nodes at a fixed period"))
+        })
+        .fold_ok(1, lcm)
+}
+
+struct Pass {
+    end: u16,
+    // Steps within the pass (1-based) landing on an end node
+    hits: Vec<usize>,
+}
+
+pub struct NodeGraph {
+    nodes: Vec<Node>,
+    // Indexed by node code
+    ids: Vec<Option<u16>>,
+    edges: Vec<[u16; 2]>,
+    pass_length: usize,
+    passes: Vec<Pass>,
+}
+
+impl NodeGraph {
+    pub fn new(map: &Map, is_end: impl Fn(Node) -> bool) -> Result<Self> {
+        if map.nodes.len() > usize::from(u16::MAX) + 1 {
+            return Err(anyhow!("Too many nodes to intern: {}", map.nodes.len()));
+        }
+        let mut ids = vec![None; NODE_CODES];
+        for (id, (node, _)) in map.nodes.iter().enumerate() {
+            let code = node
+                .code()
+                .ok_or_else(|| anyhow!("Cannot intern {node:?}"))?;
+            ids[code] = Some(id as u16);
+        }
+        let nodes = map.nodes.iter().map(|&(node, _)| node).collect_vec();
+        let edges = map
+            .nodes
+            .iter()
+            .map(|(node, edges)| {
+                let mut interned = [0; 2];
+                for (id, edge) in interned.iter_mut().zip(edges) {
+                    *id = edge
+                        .code()
+                        .and_then(|code| ids[code])
+                        .ok_or_else(|| anyhow!("{node:?} leads to unknown {edge:?}"))?;
                 }
+                Ok(interned)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut graph = NodeGraph {
+            nodes,
+            ids,
+            edges,
+            pass_length: map.directions.len(),
+            passes: Vec::new(),
+        };
+        graph.passes = (0..graph.nodes.len() as u16)
+            .map(|start| {
+                let mut current = start;
+                let mut hits = Vec::new();
+                for (i, &direction) in map.directions.iter().enumerate() {
+                    current = graph.step(current, direction);
+                    if is_end(graph.nodes[usize::from(current)]) {
+                        hits.push(i + 1);
+                    }
+                }
+                Pass { end: current, hits }
+            })
+            .collect();
+        Ok(graph)
+    }
+
+    pub fn step(&self, id: u16, direction: Direction) -> u16 {
+        self.edges[usize::from(id)][direction.into_usize()]
+    }
+
+    pub fn node(&self, id: u16) -> Node {
+        self.nodes[usize::from(id)]
+    }
+
+    pub fn id(&self, node: Node) -> Option<u16> {
+        self.ids[node.code()?]
+    }
+
+    pub fn steps_to_end(&self, start: u16) -> Option<usize> {
+        let mut current = start;
+        // After as many passes as there are nodes, a pass start repeats
+        for pass in 0..self.nodes.len() {
+            let Pass { end, hits } = &self.passes[usize::from(current)];
+            if let Some(&hit) = hits.first() {
+                return Some(pass * self.pass_length + hit);
             }
-            unreachable!()
-        })
-        .fold(1, lcm)
+            current = *end;
+        }
+        None
+    }
+
+    // Cycles are detected on pass boundaries, so the cycle may be a multiple of the shortest one
+    fn ghost_cycle(&self, start: u16) -> GhostCycle {
+        let mut seen = vec![None; self.nodes.len()];
+        let mut hits = Vec::new();
+        let mut current = start;
+        for pass in 0.. {
+            if let Some(cycle_pass) = seen[usize::from(current)] {
+                let cycle_start = cycle_pass * self.pass_length;
+                let cycle_length = (pass - cycle_pass) * self.pass_length;
+                let (prefix_hits, cycle_hits): (Vec<_>, Vec<_>) =
+                    hits.into_iter().partition(|&hit| hit < cycle_start);
+                return GhostCycle {
+                    prefix_hits,
+                    cycle_start,
+                    cycle_length,
+                    cycle_hits: cycle_hits
+                        .into_iter()
+                        .map(|hit| (hit - cycle_start) % cycle_length)
+                        .sorted()
+                        .dedup()
+                        .collect(),
+                };
+            }
+            seen[usize::from(current)] = Some(pass);
+            let Pass {
+                end,
+                hits: pass_hits,
+            } = &self.passes[usize::from(current)];
+            hits.extend(pass_hits.iter().map(|hit| pass * self.pass_length + hit));
+            current = *end;
+        }
+        unreachable!()
+    }
+}
+
+#[aoc_generator(day8, part1, fast)]
+fn parse_graph_part1(input: &str) -> Result<NodeGraph> {
+    NodeGraph::new(&parse(input)?, |node| node == ZZZ)
+}
+
+#[aoc(day8, part1, fast)]
+fn part1_fast(graph: &NodeGraph) -> Result<usize> {
+    let start = graph.id(AAA).ok_or_else(|| anyhow!("Missing {AAA:?}"))?;
+    graph
+        .steps_to_end(start)
+        .ok_or_else(|| anyhow!("{ZZZ:?} is never reached"))
+}
+
+#[aoc_generator(day8, part2, fast)]
+fn parse_graph_part2(input: &str) -> Result<NodeGraph> {
+    NodeGraph::new(&parse(input)?, |node| node.is_end())
+}
+
+#[aoc(day8, part2, fast)]
+fn part2_fast(graph: &NodeGraph) -> Result<usize> {
+    let cycles = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| node.is_start())
+        .map(|(id, _)| graph.ghost_cycle(id as u16))
+        .collect_vec();
+    first_common_step(&cycles)
 }
 
 mod parser {
@@ -143,6 +542,14 @@ ZZZ = (ZZZ, ZZZ)";
     fn part1_example() {
         assert_eq!(part1(&parse(EXAMPLE1_RL).unwrap()), 2);
         assert_eq!(part1(&parse(EXAMPLE2_LLR).unwrap()), 6);
+        assert_eq!(
+            part1_fast(&parse_graph_part1(EXAMPLE1_RL).unwrap()).unwrap(),
+            2
+        );
+        assert_eq!(
+            part1_fast(&parse_graph_part1(EXAMPLE2_LLR).unwrap()).unwrap(),
+            6
+        );
     }
 
     const EXAMPLE2: &str = "LR
@@ -158,6 +565,216 @@ XXX = (XXX, XXX)";
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(EXAMPLE2).unwrap()), 6);
+        assert_eq!(part2(&parse(EXAMPLE2).unwrap()).unwrap(), 6);
+        assert_eq!(
+            part2_fast(&parse_graph_part2(EXAMPLE2).unwrap()).unwrap(),
+            6
+        );
+        assert_eq!(part2_lcm(&parse(EXAMPLE2).unwrap()).unwrap(), 6);
+    }
+
+    #[test]
+    fn lcm_assumption() {
+        for input in [UNALIGNED_CYCLES, OFFSET_CYCLES, PREFIX_ONLY, NEVER_ALIGNED] {
+            assert!(part2_lcm(&parse(input).unwrap()).is_err());
+        }
+    }
+
+    // 11A enters its cycle before its first Z node, 22A has two Z nodes per cycle
+    const UNALIGNED_CYCLES: &str = "L
+
+11A = (11B, 11B)
+11B = (11Z, 11Z)
+11Z = (11C, 11C)
+11C = (11B, 11B)
+22A = (22Z, 22Z)
+22Z = (22B, 22B)
+22B = (23Z, 23Z)
+23Z = (22C, 22C)
+22C = (22Z, 22Z)";
+
+    #[test]
+    fn part2_unaligned_cycles() {
+        let map = parse(UNALIGNED_CYCLES).unwrap();
+        assert_eq!(part2(&map).unwrap(), 5);
+        assert_eq!(simulate_common_step(&map), 5);
+        let graph = parse_graph_part2(UNALIGNED_CYCLES).unwrap();
+        assert_eq!(part2_fast(&graph).unwrap(), 5);
+    }
+
+    const OFFSET_CYCLES: &str = "L
+
+11A = (11B, 11B)
+11B = (11Z, 11Z)
+11Z = (11C, 11C)
+11C = (11B, 11B)
+22A = (22Z, 22Z)
+22Z = (22B, 22B)
+22B = (22Z, 22Z)";
+
+    #[test]
+    fn part2_offset_cycles() {
+        let map = parse(OFFSET_CYCLES).unwrap();
+        assert_eq!(part2(&map).unwrap(), 5);
+        assert_eq!(simulate_common_step(&map), 5);
+        let graph = parse_graph_part2(OFFSET_CYCLES).unwrap();
+        assert_eq!(part2_fast(&graph).unwrap(), 5);
+    }
+
+    #[test]
+    fn dot_export() {
+        let map: Map = EXAMPLE1_RL.parse().unwrap();
+        assert_eq!(
+            map.to_dot(),
+            r#"digraph network {
+    "AAA" [style=filled, fillcolor=green];
+    "ZZZ" [style=filled, fillcolor=red];
+    "AAA" -> "BBB" [label="L"];
+    "AAA" -> "CCC" [label="R"];
+    "BBB" -> "DDD" [label="L"];
+    "BBB" -> "EEE" [label="R"];
+    "CCC" -> "ZZZ" [label="L"];
+    "CCC" -> "GGG" [label="R"];
+    "DDD" -> "DDD" [label="L"];
+    "DDD" -> "DDD" [label="R"];
+    "EEE" -> "EEE" [label="L"];
+    "EEE" -> "EEE" [label="R"];
+    "GGG" -> "GGG" [label="L"];
+    "GGG" -> "GGG" [label="R"];
+    "ZZZ" -> "ZZZ" [label="L"];
+    "ZZZ" -> "ZZZ" [label="R"];
+}
+"#
+        );
+
+        let map: Map = EXAMPLE2.parse().unwrap();
+        assert_eq!(
+            map.to_dot(),
+            r#"digraph network {
+    "11A" [style=filled, fillcolor=green];
+    "11Z" [style=filled, fillcolor=red];
+    "22A" [style=filled, fillcolor=green];
+    "22Z" [style=filled, fillcolor=red];
+    "11A" -> "11B" [label="L"];
+    "11A" -> "XXX" [label="R"];
+    "11B" -> "XXX" [label="L"];
+    "11B" -> "11Z" [label="R"];
+    "11Z" -> "11B" [label="L"];
+    "11Z" -> "XXX" [label="R"];
+    "22A" -> "22B" [label="L"];
+    "22A" -> "XXX" [label="R"];
+    "22B" -> "22C" [label="L"];
+    "22B" -> "22C" [label="R"];
+    "22C" -> "22Z" [label="L"];
+    "22C" -> "22Z" [label="R"];
+    "22Z" -> "22B" [label="L"];
+    "22Z" -> "22B" [label="R"];
+    "XXX" -> "XXX" [label="L"];
+    "XXX" -> "XXX" [label="R"];
+}
+"#
+        );
+    }
+
+    #[test]
+    fn cycle_summary() {
+        let map: Map = EXAMPLE2.parse().unwrap();
+        assert_eq!(
+            map.cycle_summary(),
+            "11A: first Z after 2 steps, cycle of 2 steps from step 1, Z offsets [1]
+22A: first Z after 3 steps, cycle of 6 steps from step 1, Z offsets [2, 5]
+"
+        );
+        let map: Map = NEVER_ALIGNED.parse().unwrap();
+        assert!(map
+            .cycle_summary()
+            .starts_with("11A: first Z after 1 steps"));
+    }
+
+    #[test]
+    fn node_graph_rejects_unknown_nodes() {
+        assert!(parse_graph_part1("L\n\nAAA = (BBB, ZZZ)\nZZZ = (ZZZ, ZZZ)").is_err());
+        assert!(parse_graph_part1("L\n\nAAA = (ZZZ, aaa)\nZZZ = (ZZZ, ZZZ)").is_err());
+        let graph = parse_graph_part2(EXAMPLE2).unwrap();
+        assert_eq!(graph.id(Node(*b"22Z")), Some(6));
+        assert_eq!(graph.id(Node(*b"33Z")), None);
+    }
+
+    #[test]
+    fn node_graph_queries() {
+        let map: Map = EXAMPLE2_LLR.parse().unwrap();
+        let graph = NodeGraph::new(&map, |node| node == "ZZZ".parse().unwrap()).unwrap();
+        let start = graph.id("AAA".parse().unwrap()).unwrap();
+        assert_eq!(graph.steps_to_end(start), Some(6));
+        let next = graph.step(start, Direction::Right);
+        assert_eq!(graph.node(next).to_string(), "BBB");
+        assert_eq!(graph.node(graph.step(next, Direction::Right)), ZZZ);
+        assert!("AAAA".parse::<Node>().is_err());
+    }
+
+    #[test]
+    fn ghost_cycle_hits_are_unique() {
+        let graph = parse_graph_part2(PREFIX_ONLY).unwrap();
+        let cycle = graph.ghost_cycle(graph.id(Node(*b"22A")).unwrap());
+        assert_eq!(cycle.cycle_hits, vec![0]);
+    }
+
+    const PREFIX_ONLY: &str = "L
+
+11A = (11Z, 11Z)
+11Z = (11B, 11B)
+11B = (11B, 11B)
+22A = (22Z, 22Z)
+22Z = (22Z, 22Z)";
+
+    #[test]
+    fn part2_prefix_only() {
+        assert_eq!(part2(&parse(PREFIX_ONLY).unwrap()).unwrap(), 1);
+        assert_eq!(
+            part2_fast(&parse_graph_part2(PREFIX_ONLY).unwrap()).unwrap(),
+            1
+        );
+    }
+
+    const NEVER_ALIGNED: &str = "L
+
+11A = (11Z, 11Z)
+11Z = (11B, 11B)
+11B = (11Z, 11Z)
+22A = (22B, 22B)
+22B = (22Z, 22Z)
+22Z = (22B, 22B)";
+
+    #[test]
+    fn part2_never_aligned() {
+        assert!(part2(&parse(NEVER_ALIGNED).unwrap()).is_err());
+        assert!(part2_fast(&parse_graph_part2(NEVER_ALIGNED).unwrap()).is_err());
+    }
+
+    #[test]
+    fn part2_simulation_fallback() {
+        let map = parse(OFFSET_CYCLES).unwrap();
+        let nodes: HashMap<_, _> = map.nodes.iter().copied().collect();
+        let cycles = [Node(*b"11A"), Node(*b"22A")]
+            .map(|start| GhostCycle::detect(&map.directions, &nodes, start));
+        assert_eq!(simulate(&cycles, 1), Some(5));
+    }
+
+    fn simulate_common_step(map: &Map) -> usize {
+        let nodes: HashMap<_, _> = map.nodes.iter().copied().collect();
+        let mut current_nodes = nodes
+            .keys()
+            .filter(|node| node.is_start())
+            .copied()
+            .collect_vec();
+        for (i, direction) in map.directions.iter().cycle().enumerate() {
+            for node in &mut current_nodes {
+                *node = nodes[node][direction.into_usize()];
+            }
+            if current_nodes.iter().all(|node| node.is_end()) {
+                return i + 1;
+            }
+        }
+        unreachable!()
     }
 }
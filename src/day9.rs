@@ -1,90 +1,165 @@
 use crate::nom_parser::to_result;
 use anyhow::Result;
 use aoc_runner_derive::{aoc, aoc_generator};
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 
 #[derive(Debug, Clone)]
 struct Oasis {
     sensors: Vec<History>,
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Extrapolation {
+    pub past: i128,
+    pub future: i128,
+}
+
 #[derive(Debug, Clone)]
-struct History {
-    values: Vec<i32>,
+pub struct History {
+    values: Vec<i64>,
 }
 
-#[aoc_generator(day9)]
-fn parse(input: &str) -> Result<Oasis> {
-    to_result(parser::oasis(input))
+// Leading and trailing diagonals of the Newton forward-difference table
+#[derive(Debug, Clone)]
+pub struct Differences {
+    first: Vec<i128>,
+    last: Vec<i128>,
 }
 
-const ZEROS: [i32; 21] = [0; 21];
+impl History {
+    pub fn differences(&self) -> Differences {
+        self.differences_with(&mut Vec::new())
+    }
 
-#[derive(Debug)]
-struct PartialSolution {
-    past: i32,
-    future: i32,
+    // Differences are computed in `scratch`, so that it can be reused across histories
+    pub fn differences_with(&self, scratch: &mut Vec<i128>) -> Differences {
+        scratch.clear();
+        scratch.extend(self.values.iter().map(|&v| i128::from(v)));
+        let mut row = scratch.as_mut_slice();
+        let mut first = Vec::new();
+        let mut last = Vec::new();
+        while !row.iter().all(|&v| v == 0) {
+            first.push(row[0]);
+            last.push(row[row.len() - 1]);
+            for i in 1..row.len() {
+                row[i - 1] = row[i] - row[i - 1];
+            }
+            let new_len = row.len() - 1;
+            row = &mut row[..new_len];
+        }
+        // A history of zeros is the zero polynomial
+        if first.is_empty() {
+            first.push(0);
+            last.push(0);
+        }
+        Differences { first, last }
+    }
+}
+
+impl FromStr for History {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        to_result(parser::history(s))
+    }
 }
 
-fn solve(oasis: Oasis) -> impl Iterator<Item = PartialSolution> {
-    oasis.sensors.into_iter().map(|mut sensor| {
-        let mut values = sensor.values.as_mut_slice();
-        let mut future = 0;
-        let mut past = [0; 2];
-        for i in 0.. {
-            if values == &ZEROS[..values.len()] {
-                let sol = PartialSolution {
-                    future,
-                    past: past[0] - past[1],
-                };
-
-                return sol;
+impl Differences {
+    pub fn degree(&self) -> usize {
+        self.first.len() - 1
+    }
+
+    pub fn forward(&self, steps: usize) -> i128 {
+        let mut diagonal = self.last.clone();
+        for _ in 0..steps {
+            for j in (0..diagonal.len() - 1).rev() {
+                diagonal[j] += diagonal[j + 1];
             }
-            future += *values.last().unwrap();
-            past[i % 2] += values[0];
-            for i in 1..values.len() {
-                let ([.., a], [b, ..]) = values.split_at_mut(i) else {
-                    panic!("Check your maths");
-                };
-                *a = *b - *a;
+        }
+        diagonal[0]
+    }
+
+    pub fn extrapolate(&self) -> Extrapolation {
+        Extrapolation {
+            past: self.backward(1),
+            future: self.forward(1),
+        }
+    }
+
+    pub fn backward(&self, steps: usize) -> i128 {
+        let mut diagonal = self.first.clone();
+        for _ in 0..steps {
+            for j in (0..diagonal.len() - 1).rev() {
+                diagonal[j] -= diagonal[j + 1];
             }
-            let new_len = values.len() - 1;
-            values = &mut values[..new_len];
         }
-        unreachable!()
-    })
+        diagonal[0]
+    }
+}
+
+impl Oasis {
+    // One difference table per history gives both directions
+    fn extrapolations(&self) -> Vec<Extrapolation> {
+        self.sensors
+            .par_iter()
+            .map_init(Vec::new, |scratch, history| {
+                history.differences_with(scratch).extrapolate()
+            })
+            .collect()
+    }
+}
+
+// aoc-runner calls the generator again for each part, so the extrapolations are kept per input
+static EXTRAPOLATIONS: Mutex<BTreeMap<String, Arc<[Extrapolation]>>> = Mutex::new(BTreeMap::new());
+
+#[aoc_generator(day9)]
+fn parse(input: &str) -> Result<Arc<[Extrapolation]>> {
+    if let Some(extrapolations) = EXTRAPOLATIONS.lock().unwrap().get(input) {
+        return Ok(extrapolations.clone());
+    }
+    let extrapolations: Arc<[_]> = to_result(parser::oasis(input))?.extrapolations().into();
+    EXTRAPOLATIONS
+        .lock()
+        .unwrap()
+        .insert(input.to_string(), extrapolations.clone());
+    Ok(extrapolations)
 }
 
 #[aoc(day9, part1)]
-fn part1(oasis: &Oasis) -> i32 {
-    solve(oasis.clone()).map(|sol| sol.future).sum()
+fn part1(extrapolations: &[Extrapolation]) -> i128 {
+    extrapolations.iter().map(|e| e.future).sum()
 }
 
 #[aoc(day9, part2)]
-fn part2(oasis: &Oasis) -> i32 {
-    solve(oasis.clone()).map(|sol| sol.past).sum()
+fn part2(extrapolations: &[Extrapolation]) -> i128 {
+    extrapolations.iter().map(|e| e.past).sum()
 }
 
 mod parser {
     use crate::day9::{History, Oasis};
-    use nom::character::complete::{i32, newline, space1};
+    use nom::character::complete::{i64, newline, space1};
     use nom::combinator::map;
     use nom::multi::separated_list1;
     use nom::IResult;
 
+    pub fn history(input: &str) -> IResult<&str, History> {
+        map(separated_list1(space1, i64), |values| History { values })(input)
+    }
+
     pub fn oasis(input: &str) -> IResult<&str, Oasis> {
-        map(
-            separated_list1(
-                newline,
-                map(separated_list1(space1, i32), |values| History { values }),
-            ),
-            |sensors| Oasis { sensors },
-        )(input)
+        map(separated_list1(newline, history), |sensors| Oasis {
+            sensors,
+        })(input)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use itertools::Itertools;
 
     const EXAMPLE: &str = "0 3 6 9 12 15
 1 3 6 10 15 21
@@ -107,4 +182,66 @@ mod tests {
         assert_eq!(part2(&parse(CORRECT_LINE).unwrap()), -4);
         assert_eq!(part2(&parse(BUGGY_LINE).unwrap()), -4);
     }
+
+    fn history(line: &str) -> History {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn both_parts_share_extrapolations() {
+        let first = parse(EXAMPLE).unwrap();
+        let second = parse(EXAMPLE).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(
+            first[2],
+            Extrapolation {
+                past: 5,
+                future: 68
+            }
+        );
+        assert!(!Arc::ptr_eq(&first, &parse(CORRECT_LINE).unwrap()));
+    }
+
+    #[test]
+    fn scratch_buffer_reuse() {
+        let mut scratch = Vec::new();
+        for line in EXAMPLE.lines().chain([CORRECT_LINE, BUGGY_LINE, "1"]) {
+            let history = history(line);
+            let differences = history.differences_with(&mut scratch);
+            assert_eq!(differences.forward(1), history.differences().forward(1));
+            assert_eq!(differences.backward(1), history.differences().backward(1));
+        }
+    }
+
+    #[test]
+    fn degree() {
+        assert_eq!(history("0 3 6 9 12 15").differences().degree(), 1);
+        assert_eq!(history("1 3 6 10 15 21").differences().degree(), 2);
+        assert_eq!(history("10 13 16 21 30 45").differences().degree(), 3);
+        assert_eq!(history("7 7 7").differences().degree(), 0);
+        assert_eq!(history("0 0 0").differences().degree(), 0);
+        assert_eq!(history("4").differences().degree(), 0);
+        assert_eq!(history("1 2 4 8").differences().degree(), 3);
+    }
+
+    #[test]
+    fn several_steps() {
+        let differences = history("1 3 6 10 15 21").differences();
+        assert_eq!(differences.forward(0), 21);
+        assert_eq!(differences.forward(3), 45);
+        assert_eq!(differences.backward(0), 1);
+        assert_eq!(differences.backward(3), 1);
+        assert_eq!(differences.backward(4), 3);
+    }
+
+    #[test]
+    fn long_history() {
+        // n^3 for n in 0..40, longer than 21 values and beyond i32 once extrapolated
+        let values = (0..40i64).map(|n| n.pow(3)).join(" ");
+        let differences = history(&values).differences();
+        assert_eq!(differences.degree(), 3);
+        assert_eq!(differences.forward(1), 64_000);
+        assert_eq!(differences.forward(2_000), 2_039i128.pow(3));
+        assert_eq!(differences.backward(10), -1_000);
+    }
 }
@@ -0,0 +1,156 @@
+use smallvec::SmallVec;
+use std::collections::HashSet;
+use std::ops::{Index, IndexMut};
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum Direction {
+    North,
+    East,
+    South,
+    West,
+}
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct Position {
+    pub row: usize,
+    pub col: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Grid<T> {
+    rows: Vec<Vec<T>>,
+}
+
+impl<T> Grid<T> {
+    pub fn new(rows: Vec<Vec<T>>) -> Self {
+        Grid { rows }
+    }
+
+    pub fn get(&self, position: Position) -> Option<&T> {
+        self.rows.get(position.row)?.get(position.col)
+    }
+
+    // Neighbouring position, None when it would leave the grid
+    pub fn step(&self, position: Position, direction: Direction) -> Option<Position> {
+        let Position { row, col } = position;
+        let next = match direction {
+            Direction::North => Position {
+                row: row.checked_sub(1)?,
+                col,
+            },
+            Direction::South => Position { row: row + 1, col },
+            Direction::West => Position {
+                row,
+                col: col.checked_sub(1)?,
+            },
+            Direction::East => Position { row, col: col + 1 },
+        };
+        self.get(next).map(|_| next)
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Position> + '_ {
+        self.rows
+            .iter()
+            .enumerate()
+            .flat_map(|(row, cells)| (0..cells.len()).map(move |col| Position { row, col }))
+    }
+}
+
+impl<T> Index<Position> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, index: Position) -> &Self::Output {
+        &self.rows[index.row][index.col]
+    }
+}
+
+impl<T> IndexMut<Position> for Grid<T> {
+    fn index_mut(&mut self, index: Position) -> &mut Self::Output {
+        &mut self.rows[index.row][index.col]
+    }
+}
+
+// Each state is a cell entered while heading in a direction, the cell then decides where to head
+// next. States are returned in visiting order, which is the path itself when nothing branches.
+// A state is never visited twice, so loops and splitters feeding each other terminate.
+pub fn walk<T, F>(
+    grid: &Grid<T>,
+    start: Position,
+    direction: Direction,
+    transition: F,
+) -> Vec<(Position, Direction)>
+where
+    F: Fn(&T, Direction) -> SmallVec<[Direction; 2]>,
+{
+    let mut visited = HashSet::new();
+    let mut path = Vec::new();
+    let mut pending = vec![(start, direction)];
+    while let Some((position, direction)) = pending.pop() {
+        if !visited.insert((position, direction)) {
+            continue;
+        }
+        path.push((position, direction));
+        let next = transition(&grid[position], direction);
+        pending.extend(next.into_iter().rev().filter_map(|direction| {
+            grid.step(position, direction)
+                .map(|position| (position, direction))
+        }));
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smallvec::smallvec;
+
+    // `|` and `-` split a beam crossing them, `/` and `\` mirror it
+    fn beam(cell: &char, direction: Direction) -> SmallVec<[Direction; 2]> {
+        use Direction::*;
+        match (cell, direction) {
+            ('|', East | West) => smallvec![North, South],
+            ('-', North | South) => smallvec![East, West],
+            ('/', East) | ('\\', West) => smallvec![North],
+            ('/', West) | ('\\', East) => smallvec![South],
+            ('/', North) | ('\\', South) => smallvec![East],
+            ('/', South) | ('\\', North) => smallvec![West],
+            _ => smallvec![direction],
+        }
+    }
+
+    fn grid(input: &str) -> Grid<char> {
+        Grid::new(input.lines().map(|line| line.chars().collect()).collect())
+    }
+
+    #[test]
+    fn branching_walk() {
+        let grid = grid(
+            r".|...\....
+|.-.\.....
+.....|-...
+........|.
+..........
+.........\
+..../.\\..
+.-.-/..|..
+.|....-|.\
+..//.|....",
+        );
+        let path = walk(&grid, Position { row: 0, col: 0 }, Direction::East, beam);
+        let energized: HashSet<_> = path.iter().map(|&(position, _)| position).collect();
+        assert_eq!(energized.len(), 46);
+    }
+
+    #[test]
+    fn loops_terminate() {
+        let grid = grid(
+            r"/-\
+|.|
+\-/",
+        );
+        let path = walk(&grid, Position { row: 0, col: 1 }, Direction::East, beam);
+        assert_eq!(path.len(), 8);
+        assert_eq!(path[0], (Position { row: 0, col: 1 }, Direction::East));
+        assert_eq!(path[7], (Position { row: 0, col: 0 }, Direction::North));
+    }
+}
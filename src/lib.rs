@@ -1,19 +1,22 @@
 mod day16;
 mod day15;
 mod day14;
-mod day1;
+pub mod day1;
 mod day10;
-mod day11;
-mod day12;
+pub mod day11;
+pub mod day12;
 mod day13;
-mod day2;
-mod day3;
-mod day4;
+pub mod day2;
+pub mod day3;
+pub mod day4;
 mod day5;
 mod day6;
 mod day7;
-mod day8;
-mod day9;
+pub mod day8;
+pub mod day9;
+pub mod cycle;
+pub mod memo;
+mod grid;
 mod new_type;
 mod nom_parser;
 
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// A cache for one recursion tree, build one per rayon task rather than sharing it
+#[derive(Debug, Clone)]
+pub struct Memo<K, V> {
+    cache: HashMap<K, V>,
+}
+
+impl<K, V> Default for Memo<K, V> {
+    fn default() -> Self {
+        Memo {
+            cache: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V: Clone> Memo<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // `compute` gets the memo back so that it can recurse through it
+    pub fn get_or_compute(&mut self, key: K, compute: impl FnOnce(&mut Self) -> V) -> V {
+        if let Some(value) = self.cache.get(&key) {
+            return value.clone();
+        }
+        let value = compute(self);
+        self.cache.insert(key, value.clone());
+        value
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rayon::prelude::*;
+
+    fn fibonacci(n: u64, memo: &mut Memo<u64, u128>) -> u128 {
+        memo.get_or_compute(n, |memo| match n {
+            0 | 1 => n.into(),
+            n => fibonacci(n - 1, memo) + fibonacci(n - 2, memo),
+        })
+    }
+
+    #[test]
+    fn recursive_calls() {
+        let mut memo = Memo::new();
+        assert_eq!(fibonacci(150, &mut memo), 9969216677189303386214405760200);
+        assert_eq!(memo.len(), 151);
+    }
+
+    #[test]
+    fn one_memo_per_task() {
+        let values: Vec<_> = (80..90)
+            .into_par_iter()
+            .map(|n| fibonacci(n, &mut Memo::new()))
+            .collect();
+        assert!(values.windows(3).all(|w| w[0] + w[1] == w[2]));
+    }
+}
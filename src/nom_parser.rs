@@ -1,5 +1,8 @@
 use nom::bytes::complete::take_while1;
+use nom::character::complete::space1;
+use nom::combinator::recognize;
 use nom::error::ErrorKind;
+use nom::multi::separated_list1;
 use nom::IResult;
 use std::error::Error;
 use std::str::FromStr;
@@ -14,6 +17,23 @@ pub fn number<T: FromStr>(input: &str) -> IResult<&str, T> {
     ))
 }
 
+// Digits separated by spaces, read as a single number: `7  15   30` is `71530`
+pub fn number_with_spaces<T: FromStr>(input: &str) -> IResult<&str, T> {
+    let (input, number_str) = recognize(separated_list1(
+        space1,
+        take_while1(|c: char| c.is_ascii_digit()),
+    ))(input)?;
+    Ok((
+        input,
+        number_str
+            .chars()
+            .filter(char::is_ascii_digit)
+            .collect::<String>()
+            .parse()
+            .map_err(|_| nom::Err::Failure(nom::error::Error::new(number_str, ErrorKind::Fail)))?,
+    ))
+}
+
 pub trait ErrToOwned {
     type Owned;
 